@@ -0,0 +1,239 @@
+//! Optimizer throughput on a 100x100 grid of 500 random materials
+//!
+//! Run with `cargo bench --bench optimizer`; prints swap iterations per second for the
+//! `TileGrid` optimizer and for a baseline that keeps the path-keyed grid it replaced.
+
+use mosaic_rust::adjacency::{
+    AdjacencyKernel, AdjacencyPenaltyCalculator, GridPosition, Neighborhood,
+};
+use mosaic_rust::optimizer::{MosaicOptimizer, MoveSet, OptimizationConfig};
+use mosaic_rust::similarity::SimilarityDatabase;
+use mosaic_rust::tile_grid::{TileGrid, TileId};
+use palette::Lab;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+const GRID: usize = 100;
+const MATERIALS: usize = 500;
+const ITERATIONS: usize = 500_000;
+/// Runs per case; the fastest is reported to keep scheduler noise out
+const RUNS: usize = 5;
+const SEED: u64 = 1;
+
+type PathGrid = Vec<Vec<Option<PathBuf>>>;
+
+/// Swap-only annealing on the representation used before `TileGrid`: rows of material
+/// paths, with every similarity looked up through a path-to-index map into a triangular
+/// matrix, as `SimilarityDatabase::get_similarity` did
+struct PathBaseline {
+    path_to_index: HashMap<PathBuf, usize>,
+    /// Upper triangle of the similarity matrix, row by row
+    similarities: Vec<f32>,
+    kernel: AdjacencyKernel,
+    offsets: Vec<(isize, isize, f32)>,
+}
+
+impl PathBaseline {
+    fn new(db: &SimilarityDatabase, kernel: AdjacencyKernel) -> Self {
+        let mut similarities = Vec::with_capacity(MATERIALS * (MATERIALS - 1) / 2);
+        for i in 0..MATERIALS {
+            for j in i + 1..MATERIALS {
+                similarities.push(
+                    db.similarity(TileId::new(i), TileId::new(j))
+                        .expect("both tiles are in the database"),
+                );
+            }
+        }
+        Self {
+            path_to_index: (0..MATERIALS).map(|i| (material_path(i), i)).collect(),
+            similarities,
+            kernel,
+            offsets: kernel.offsets(),
+        }
+    }
+
+    fn get_similarity(&self, path1: &Path, path2: &Path) -> Option<f32> {
+        let idx1 = self.path_to_index.get(path1)?;
+        let idx2 = self.path_to_index.get(path2)?;
+        if idx1 == idx2 {
+            return Some(0.0);
+        }
+        let (i, j) = if idx1 < idx2 {
+            (*idx1, *idx2)
+        } else {
+            (*idx2, *idx1)
+        };
+        let position = i * MATERIALS - i * (i + 1) / 2 + j - i - 1;
+        self.similarities.get(position).copied()
+    }
+
+    fn pair_cost(&self, a: &Path, b: &Path, distance_weight: f32) -> f32 {
+        match self.get_similarity(a, b) {
+            Some(similarity) => distance_weight * self.kernel.penalty(similarity),
+            None => 0.0,
+        }
+    }
+
+    fn neighbors(&self, position: GridPosition) -> impl Iterator<Item = (GridPosition, f32)> + '_ {
+        self.offsets.iter().filter_map(move |&(dx, dy, weight)| {
+            let x = position.x.checked_add_signed(dx).filter(|&x| x < GRID)?;
+            let y = position.y.checked_add_signed(dy).filter(|&y| y < GRID)?;
+            Some((GridPosition::new(x, y), weight))
+        })
+    }
+
+    fn total_cost(&self, grid: &PathGrid) -> f32 {
+        let mut total = 0.0;
+        for y in 0..GRID {
+            for x in 0..GRID {
+                let Some(path) = &grid[y][x] else { continue };
+                for (neighbor, weight) in self.neighbors(GridPosition::new(x, y)) {
+                    if (neighbor.y, neighbor.x) <= (y, x) {
+                        continue;
+                    }
+                    if let Some(neighbor_path) = &grid[neighbor.y][neighbor.x] {
+                        total += self.pair_cost(path, neighbor_path, weight);
+                    }
+                }
+            }
+        }
+        total
+    }
+
+    fn swap_delta(&self, grid: &PathGrid, pos1: GridPosition, pos2: GridPosition) -> f32 {
+        let (Some(path1), Some(path2)) = (&grid[pos1.y][pos1.x], &grid[pos2.y][pos2.x]) else {
+            return 0.0;
+        };
+        if path1 == path2 {
+            return 0.0;
+        }
+        let mut old_cost = 0.0;
+        let mut new_cost = 0.0;
+        for (pos, old_path, new_path, other) in
+            [(pos1, path1, path2, pos2), (pos2, path2, path1, pos1)]
+        {
+            for (neighbor, weight) in self.neighbors(pos) {
+                if neighbor == other {
+                    continue;
+                }
+                if let Some(neighbor_path) = &grid[neighbor.y][neighbor.x] {
+                    old_cost += self.pair_cost(old_path, neighbor_path, weight);
+                    new_cost += self.pair_cost(new_path, neighbor_path, weight);
+                }
+            }
+        }
+        new_cost - old_cost
+    }
+
+    /// Anneal like `MosaicOptimizer::optimize` with swaps only, returning the final cost
+    fn optimize(&self, grid: &mut PathGrid, config: &OptimizationConfig) -> f32 {
+        let mut rng = StdRng::seed_from_u64(SEED);
+        let mut cost = self.total_cost(grid);
+        let mut temperature = config.initial_temperature;
+        for _ in 0..config.max_iterations {
+            let pos1 = GridPosition::new(rng.gen_range(0..GRID), rng.gen_range(0..GRID));
+            let pos2 = GridPosition::new(rng.gen_range(0..GRID), rng.gen_range(0..GRID));
+            if pos1 != pos2 && grid[pos1.y][pos1.x].is_some() && grid[pos2.y][pos2.x].is_some() {
+                let delta = self.swap_delta(grid, pos1, pos2);
+                let accept = delta < 0.0 || rng.gen::<f32>() < (-delta / temperature).exp();
+                if accept {
+                    let tile = grid[pos1.y][pos1.x].clone();
+                    grid[pos1.y][pos1.x] = grid[pos2.y][pos2.x].clone();
+                    grid[pos2.y][pos2.x] = tile;
+                    cost += delta;
+                }
+            }
+            temperature *= config.temperature_decay;
+        }
+        cost
+    }
+}
+
+fn material_path(index: usize) -> PathBuf {
+    format!("materials/tile_{index:04}.jpg").into()
+}
+
+/// Fastest of `RUNS` timings of `run`, in iterations per second, with the final cost
+fn measure(mut run: impl FnMut() -> f32) -> (f64, f32) {
+    let mut fastest = f64::INFINITY;
+    let mut cost = 0.0;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        cost = run();
+        fastest = fastest.min(start.elapsed().as_secs_f64());
+    }
+    (ITERATIONS as f64 / fastest, cost)
+}
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut db = SimilarityDatabase::new();
+    for i in 0..MATERIALS {
+        db.add_tile(
+            material_path(i),
+            Lab::new(
+                rng.gen_range(0.0..100.0),
+                rng.gen_range(-60.0..60.0),
+                rng.gen_range(-60.0..60.0),
+            ),
+        );
+    }
+    db.build_similarities();
+    let grid = TileGrid::from_rows(
+        (0..GRID)
+            .map(|_| {
+                (0..GRID)
+                    .map(|_| Some(TileId::new(rng.gen_range(0..MATERIALS))))
+                    .collect()
+            })
+            .collect(),
+    );
+    let path_grid: PathGrid = grid
+        .rows()
+        .map(|row| {
+            row.iter()
+                .map(|tile| tile.map(|tile| material_path(tile.index())))
+                .collect()
+        })
+        .collect();
+
+    let config = OptimizationConfig {
+        max_iterations: ITERATIONS,
+        report_interval: usize::MAX,
+        seed: Some(SEED),
+        moves: MoveSet::SWAP_ONLY,
+        ..Default::default()
+    };
+    for (name, kernel) in [
+        ("4-neighborhood", AdjacencyKernel::default()),
+        (
+            "8-neighborhood, radius 2",
+            AdjacencyKernel {
+                neighborhood: Neighborhood::Eight,
+                radius: 2,
+                ..Default::default()
+            },
+        ),
+    ] {
+        let baseline = PathBaseline::new(&db, kernel);
+        let (path_rate, path_cost) = measure(|| {
+            let mut grid = path_grid.clone();
+            baseline.optimize(&mut grid, &config)
+        });
+
+        let calculator = AdjacencyPenaltyCalculator::new(&db, 1.0).with_kernel(kernel);
+        let optimizer = MosaicOptimizer::new(&calculator, config.clone());
+        let (id_rate, id_cost) = measure(|| {
+            let mut grid = grid.clone();
+            optimizer.optimize(&mut grid).final_cost
+        });
+
+        println!("{name}:");
+        println!("  path grid:   {path_rate:>10.0} iterations/s, final cost {path_cost:.3}");
+        println!("  TileId grid: {id_rate:>10.0} iterations/s, final cost {id_cost:.3}");
+        println!("  speedup:     {:.1}x", id_rate / path_rate);
+    }
+}
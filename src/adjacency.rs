@@ -1,5 +1,11 @@
 use crate::similarity::SimilarityDatabase;
-use std::path::Path;
+use crate::tile_grid::{TileGrid, TileId};
+use anyhow::{bail, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// Default ΔE scale of the exponential curve and cutoff of the threshold curve
+pub const DEFAULT_CURVE_DELTA_E: f32 = 10.0;
 
 /// Represents a position in the grid
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -45,42 +51,230 @@ impl GridPosition {
     }
 }
 
+/// Which cells around a tile count as its neighbors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Neighborhood {
+    /// Cells within the radius in horizontal plus vertical steps
+    #[default]
+    Four,
+    /// Cells within the radius in any direction, diagonals included
+    Eight,
+}
+
+impl FromStr for Neighborhood {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "4" | "four" => Ok(Self::Four),
+            "8" | "eight" => Ok(Self::Eight),
+            _ => bail!("Unknown neighborhood {s:?} (expected 4 or 8)"),
+        }
+    }
+}
+
+impl fmt::Display for Neighborhood {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Four => f.write_str("4"),
+            Self::Eight => f.write_str("8"),
+        }
+    }
+}
+
+/// How the penalty of a neighboring pair falls off with the color distance of its tiles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PenaltyCurve {
+    /// `1 / (ΔE + 1)`
+    #[default]
+    Inverse,
+    /// `exp(-ΔE / scale)`
+    Exponential,
+    /// 1 below the ΔE cutoff, 0 from it on
+    Threshold,
+}
+
+impl FromStr for PenaltyCurve {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "inverse" => Ok(Self::Inverse),
+            "exponential" => Ok(Self::Exponential),
+            "threshold" => Ok(Self::Threshold),
+            _ => bail!("Unknown penalty curve {s:?} (expected inverse, exponential or threshold)"),
+        }
+    }
+}
+
+impl fmt::Display for PenaltyCurve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Inverse => "inverse",
+            Self::Exponential => "exponential",
+            Self::Threshold => "threshold",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Neighbors a tile is compared with, and how much each comparison costs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdjacencyKernel {
+    pub neighborhood: Neighborhood,
+    /// Reach of the kernel in cells; farther neighbors are weighted by `1 / distance`
+    pub radius: usize,
+    pub curve: PenaltyCurve,
+    /// ΔE scale of the exponential curve and cutoff of the threshold curve
+    pub delta_e: f32,
+}
+
+impl Default for AdjacencyKernel {
+    fn default() -> Self {
+        Self {
+            neighborhood: Neighborhood::Four,
+            radius: 1,
+            curve: PenaltyCurve::Inverse,
+            delta_e: DEFAULT_CURVE_DELTA_E,
+        }
+    }
+}
+
+impl AdjacencyKernel {
+    /// Offsets of every neighbor within reach, with their distance weights
+    pub fn offsets(&self) -> Vec<(isize, isize, f32)> {
+        let radius = self.radius.max(1) as isize;
+        let mut offsets = Vec::new();
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let within = match self.neighborhood {
+                    Neighborhood::Four => dx.abs() + dy.abs() <= radius,
+                    Neighborhood::Eight => true,
+                };
+                if within && (dx, dy) != (0, 0) {
+                    let distance = ((dx * dx + dy * dy) as f32).sqrt();
+                    offsets.push((dx, dy, 1.0 / distance));
+                }
+            }
+        }
+        offsets
+    }
+
+    /// Penalty of a pair of tiles `delta_e` apart, before any weights
+    pub fn penalty(&self, delta_e: f32) -> f32 {
+        match self.curve {
+            PenaltyCurve::Inverse => 1.0 / (delta_e + 1.0),
+            PenaltyCurve::Exponential => (-delta_e / self.delta_e.max(f32::EPSILON)).exp(),
+            PenaltyCurve::Threshold => {
+                if delta_e < self.delta_e {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
 /// Manages adjacency penalties for tile placement
 pub struct AdjacencyPenaltyCalculator<'a> {
     similarity_db: &'a SimilarityDatabase,
     penalty_weight: f32,
+    cell_weights: Option<&'a [Vec<f32>]>,
+    kernel: AdjacencyKernel,
+    /// `kernel.offsets()`, computed once
+    offsets: Vec<(isize, isize, f32)>,
 }
 
 impl<'a> AdjacencyPenaltyCalculator<'a> {
     pub fn new(similarity_db: &'a SimilarityDatabase, penalty_weight: f32) -> Self {
+        let kernel = AdjacencyKernel::default();
         Self {
             similarity_db,
             penalty_weight,
+            cell_weights: None,
+            kernel,
+            offsets: kernel.offsets(),
         }
     }
 
-    /// Calculate the adjacency penalty for placing a tile at a specific position
-    pub fn calculate_penalty(
+    /// Weight [`Self::calculate_penalty`] applies to the raw adjacency cost
+    pub fn penalty_weight(&self) -> f32 {
+        self.penalty_weight
+    }
+
+    /// Scale the cost of each neighboring pair by the mean weight of its two cells
+    pub fn with_cell_weights(mut self, cell_weights: &'a [Vec<f32>]) -> Self {
+        self.cell_weights = Some(cell_weights);
+        self
+    }
+
+    /// Compare tiles with the neighbors of `kernel` instead of the 4 adjacent cells
+    pub fn with_kernel(mut self, kernel: AdjacencyKernel) -> Self {
+        self.offsets = kernel.offsets();
+        self.kernel = kernel;
+        self
+    }
+
+    /// Weight of the pair of cells at `a` and `b` (1.0 without cell weights)
+    fn pair_weight(&self, a: GridPosition, b: GridPosition) -> f32 {
+        match self.cell_weights {
+            Some(weights) => (weights[a.y][a.x] + weights[b.y][b.x]) / 2.0,
+            None => 1.0,
+        }
+    }
+
+    /// Cells within the kernel around `position`, with their distance weights
+    fn neighbors(
         &self,
-        candidate_path: &Path,
         position: GridPosition,
-        grid: &[Vec<Option<std::path::PathBuf>>],
         grid_width: usize,
         grid_height: usize,
+    ) -> impl Iterator<Item = (GridPosition, f32)> + '_ {
+        self.offsets.iter().filter_map(move |&(dx, dy, weight)| {
+            let x = position
+                .x
+                .checked_add_signed(dx)
+                .filter(|&x| x < grid_width)?;
+            let y = position
+                .y
+                .checked_add_signed(dy)
+                .filter(|&y| y < grid_height)?;
+            Some((GridPosition::new(x, y), weight))
+        })
+    }
+
+    /// Unscaled cost of tile `a_id` at `a` next to `b_id` at `b`, or 0.0 when either tile
+    /// is not in the similarity database
+    fn pair_cost(
+        &self,
+        a_id: TileId,
+        a: GridPosition,
+        b_id: TileId,
+        b: GridPosition,
+        distance_weight: f32,
+    ) -> f32 {
+        match self.similarity_db.similarity(a_id, b_id) {
+            // Higher similarity (smaller distance) results in higher penalty
+            Some(similarity) => {
+                distance_weight * self.pair_weight(a, b) * self.kernel.penalty(similarity)
+            }
+            None => 0.0,
+        }
+    }
+
+    /// Calculate the adjacency penalty for placing a tile at a specific position
+    pub fn calculate_penalty(
+        &self,
+        candidate: TileId,
+        position: GridPosition,
+        grid: &TileGrid,
     ) -> f32 {
-        let adjacent_positions = position.get_adjacent_positions(grid_width, grid_height);
         let mut penalty = 0.0;
 
-        for adj_pos in adjacent_positions {
-            if let Some(neighbor_path) = &grid[adj_pos.y][adj_pos.x] {
-                if let Some(similarity) = self
-                    .similarity_db
-                    .get_similarity(candidate_path, neighbor_path)
-                {
-                    // Higher similarity (smaller distance) results in higher penalty
-                    // Using inverse with offset to avoid division by zero
-                    penalty += 1.0 / (similarity + 1.0);
-                }
+        for (neighbor, weight) in self.neighbors(position, grid.width(), grid.height()) {
+            if let Some(neighbor_id) = grid.get(neighbor.x, neighbor.y) {
+                penalty += self.pair_cost(candidate, position, neighbor_id, neighbor, weight);
             }
         }
 
@@ -88,40 +282,18 @@ impl<'a> AdjacencyPenaltyCalculator<'a> {
     }
 
     /// Calculate total adjacency cost for the entire grid
-    pub fn calculate_total_cost(&self, grid: &[Vec<Option<std::path::PathBuf>>]) -> f32 {
-        let grid_height = grid.len();
-        if grid_height == 0 {
-            return 0.0;
-        }
-        let grid_width = grid[0].len();
-
+    pub fn calculate_total_cost(&self, grid: &TileGrid) -> f32 {
         let mut total_cost = 0.0;
 
-        for y in 0..grid_height {
-            for x in 0..grid_width {
-                if let Some(current_path) = &grid[y][x] {
-                    // Only check right and down to avoid double counting
-                    // Right neighbor
-                    if x < grid_width - 1 {
-                        if let Some(right_path) = &grid[y][x + 1] {
-                            if let Some(similarity) =
-                                self.similarity_db.get_similarity(current_path, right_path)
-                            {
-                                total_cost += 1.0 / (similarity + 1.0);
-                            }
-                        }
-                    }
-
-                    // Down neighbor
-                    if y < grid_height - 1 {
-                        if let Some(down_path) = &grid[y + 1][x] {
-                            if let Some(similarity) =
-                                self.similarity_db.get_similarity(current_path, down_path)
-                            {
-                                total_cost += 1.0 / (similarity + 1.0);
-                            }
-                        }
-                    }
+        for (position, current_id) in grid.placed() {
+            for (neighbor, weight) in self.neighbors(position, grid.width(), grid.height()) {
+                // Only count pairs whose other cell comes later, to avoid double counting
+                if (neighbor.y, neighbor.x) <= (position.y, position.x) {
+                    continue;
+                }
+                if let Some(neighbor_id) = grid.get(neighbor.x, neighbor.y) {
+                    total_cost +=
+                        self.pair_cost(current_id, position, neighbor_id, neighbor, weight);
                 }
             }
         }
@@ -132,81 +304,74 @@ impl<'a> AdjacencyPenaltyCalculator<'a> {
     /// Calculate the change in cost if two positions are swapped
     pub fn calculate_swap_delta(
         &self,
-        grid: &[Vec<Option<std::path::PathBuf>>],
+        grid: &TileGrid,
         pos1: GridPosition,
         pos2: GridPosition,
     ) -> f32 {
-        let grid_height = grid.len();
-        if grid_height == 0 {
+        // Get the tiles at both positions
+        let (Some(id1), Some(id2)) = (grid.get(pos1.x, pos1.y), grid.get(pos2.x, pos2.y)) else {
             return 0.0;
-        }
-        let grid_width = grid[0].len();
-
-        // Get the paths at both positions
-        let path1 = match &grid[pos1.y][pos1.x] {
-            Some(p) => p,
-            None => return 0.0,
-        };
-        let path2 = match &grid[pos2.y][pos2.x] {
-            Some(p) => p,
-            None => return 0.0,
         };
 
-        // If same path, no change
-        if path1 == path2 {
+        // If same tile, no change
+        if id1 == id2 {
             return 0.0;
         }
 
         let mut old_cost = 0.0;
         let mut new_cost = 0.0;
 
-        // Calculate cost changes for pos1's neighbors
-        let adj1 = pos1.get_adjacent_positions(grid_width, grid_height);
-        for adj_pos in &adj1 {
-            // Skip if it's pos2 (will be handled separately)
-            if *adj_pos == pos2 {
-                continue;
-            }
-
-            if let Some(adj_path) = &grid[adj_pos.y][adj_pos.x] {
-                // Old cost with path1 at pos1
-                if let Some(old_sim) = self.similarity_db.get_similarity(path1, adj_path) {
-                    old_cost += 1.0 / (old_sim + 1.0);
+        // Each moved tile's pairs with its unmoved neighbors change. If the two positions
+        // are within each other's kernel, their mutual pair keeps the same tiles and the
+        // same weights, so it is skipped on both sides.
+        for (pos, old_id, new_id, other) in [(pos1, id1, id2, pos2), (pos2, id2, id1, pos1)] {
+            for (neighbor, weight) in self.neighbors(pos, grid.width(), grid.height()) {
+                if neighbor == other {
+                    continue;
                 }
-                // New cost with path2 at pos1
-                if let Some(new_sim) = self.similarity_db.get_similarity(path2, adj_path) {
-                    new_cost += 1.0 / (new_sim + 1.0);
+                if let Some(neighbor_id) = grid.get(neighbor.x, neighbor.y) {
+                    old_cost += self.pair_cost(old_id, pos, neighbor_id, neighbor, weight);
+                    new_cost += self.pair_cost(new_id, pos, neighbor_id, neighbor, weight);
                 }
             }
         }
 
-        // Calculate cost changes for pos2's neighbors
-        let adj2 = pos2.get_adjacent_positions(grid_width, grid_height);
-        for adj_pos in &adj2 {
-            // Skip if it's pos1 (will be handled separately)
-            if *adj_pos == pos1 {
-                continue;
-            }
+        new_cost - old_cost
+    }
 
-            if let Some(adj_path) = &grid[adj_pos.y][adj_pos.x] {
-                // Old cost with path2 at pos2
-                if let Some(old_sim) = self.similarity_db.get_similarity(path2, adj_path) {
-                    old_cost += 1.0 / (old_sim + 1.0);
+    /// Calculate the change in cost if each listed cell took its new tile at once
+    ///
+    /// Covers moves that touch several cells, such as rotating three tiles or replacing one.
+    /// Pairs of two changed cells are counted once, with both new tiles.
+    pub fn calculate_change_delta(
+        &self,
+        grid: &TileGrid,
+        changes: &[(GridPosition, TileId)],
+    ) -> f32 {
+        let new_tile = |cell: GridPosition| {
+            changes
+                .iter()
+                .position(|&(changed, _)| changed == cell)
+                .map(|index| (index, changes[index].1))
+        };
+
+        let mut old_cost = 0.0;
+        let mut new_cost = 0.0;
+        for (index, &(pos, new_id)) in changes.iter().enumerate() {
+            let old_id = grid.get(pos.x, pos.y);
+            for (neighbor, weight) in self.neighbors(pos, grid.width(), grid.height()) {
+                let changed = new_tile(neighbor);
+                // The earlier cell of a changed pair already counted it
+                if changed.is_some_and(|(other, _)| other < index) {
+                    continue;
                 }
-                // New cost with path1 at pos2
-                if let Some(new_sim) = self.similarity_db.get_similarity(path1, adj_path) {
-                    new_cost += 1.0 / (new_sim + 1.0);
+                let old_neighbor = grid.get(neighbor.x, neighbor.y);
+                if let (Some(old_id), Some(neighbor_id)) = (old_id, old_neighbor) {
+                    old_cost += self.pair_cost(old_id, pos, neighbor_id, neighbor, weight);
+                }
+                if let Some(neighbor_id) = changed.map(|(_, tile)| tile).or(old_neighbor) {
+                    new_cost += self.pair_cost(new_id, pos, neighbor_id, neighbor, weight);
                 }
-            }
-        }
-
-        // If pos1 and pos2 are adjacent, handle their mutual cost
-        if adj1.contains(&pos2) {
-            // Their similarity remains the same after swap, so no change in cost
-            if let Some(sim) = self.similarity_db.get_similarity(path1, path2) {
-                let cost = 1.0 / (sim + 1.0);
-                old_cost += cost;
-                new_cost += cost;
             }
         }
 
@@ -219,7 +384,12 @@ mod tests {
     use super::*;
     use crate::similarity::SimilarityDatabase;
     use palette::Lab;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
+
+    /// Id of a tile added to `sim_db`
+    fn id(sim_db: &SimilarityDatabase, name: &str) -> TileId {
+        sim_db.tile_id(Path::new(name)).unwrap()
+    }
 
     #[test]
     fn test_grid_position_adjacent() {
@@ -253,18 +423,13 @@ mod tests {
         let calculator = AdjacencyPenaltyCalculator::new(&sim_db, 1.0);
 
         // Create a simple grid
-        let mut grid = vec![vec![None; 3]; 3];
-        grid[0][0] = Some(PathBuf::from("tile1.png"));
-        grid[0][1] = Some(PathBuf::from("tile2.png"));
-
-        // Calculate penalty for placing tile3 at position (1, 0)
-        let penalty = calculator.calculate_penalty(
-            Path::new("tile3.png"),
-            GridPosition::new(1, 0),
-            &grid,
-            3,
-            3,
-        );
+        let mut grid = TileGrid::new(3, 3);
+        grid.set(0, 0, Some(id(&sim_db, "tile1.png")));
+        grid.set(1, 0, Some(id(&sim_db, "tile2.png")));
+
+        // Calculate penalty for placing tile3 at position (1, 1)
+        let penalty =
+            calculator.calculate_penalty(id(&sim_db, "tile3.png"), GridPosition::new(1, 1), &grid);
 
         // Should have penalty from tile2 neighbor
         assert!(penalty > 0.0);
@@ -279,13 +444,263 @@ mod tests {
 
         let calculator = AdjacencyPenaltyCalculator::new(&sim_db, 1.0);
 
-        let mut grid = vec![vec![None; 2]; 2];
-        grid[0][0] = Some(PathBuf::from("tile1.png"));
-        grid[0][1] = Some(PathBuf::from("tile2.png"));
+        let mut grid = TileGrid::new(2, 2);
+        grid.set(0, 0, Some(id(&sim_db, "tile1.png")));
+        grid.set(1, 0, Some(id(&sim_db, "tile2.png")));
 
         let total_cost = calculator.calculate_total_cost(&grid);
 
         // Should have high cost since tiles are identical (similarity = 0)
         assert!(total_cost > 0.5);
     }
+    #[test]
+    fn test_cell_weights_scale_costs_consistently() {
+        let mut sim_db = SimilarityDatabase::new();
+        for (name, l) in [("a.png", 20.0), ("b.png", 40.0), ("c.png", 60.0)] {
+            sim_db.add_tile(PathBuf::from(name), Lab::new(l, 0.0, 0.0));
+        }
+        sim_db.build_similarities();
+
+        let tile = |name: &str| Some(id(&sim_db, name));
+        let mut grid = TileGrid::from_rows(vec![
+            vec![tile("a.png"), tile("b.png"), tile("c.png")],
+            vec![tile("c.png"), tile("a.png"), tile("b.png")],
+        ]);
+        let weights = vec![vec![1.0, 4.0, 1.0], vec![1.0, 1.0, 2.0]];
+        let plain = AdjacencyPenaltyCalculator::new(&sim_db, 1.0);
+        let weighted = AdjacencyPenaltyCalculator::new(&sim_db, 1.0).with_cell_weights(&weights);
+
+        assert!(weighted.calculate_total_cost(&grid) > plain.calculate_total_cost(&grid));
+
+        // The swap delta still matches the change in total cost
+        let (pos1, pos2) = (GridPosition::new(1, 0), GridPosition::new(2, 1));
+        let before = weighted.calculate_total_cost(&grid);
+        let delta = weighted.calculate_swap_delta(&grid, pos1, pos2);
+        grid.swap(pos1, pos2);
+        let after = weighted.calculate_total_cost(&grid);
+        assert!(
+            (after - before - delta).abs() < 1e-5,
+            "{delta} vs {}",
+            after - before
+        );
+    }
+
+    #[test]
+    fn test_kernel_offsets_and_curves() {
+        // The default kernel is the 4 adjacent cells at full weight
+        let default = AdjacencyKernel::default().offsets();
+        assert_eq!(default.len(), 4);
+        assert!(default.iter().all(|&(_, _, weight)| weight == 1.0));
+
+        let kernel = |neighborhood, radius| AdjacencyKernel {
+            neighborhood,
+            radius,
+            ..Default::default()
+        };
+        let eight = kernel(Neighborhood::Eight, 1).offsets();
+        assert_eq!(eight.len(), 8);
+        let diagonal = eight
+            .iter()
+            .find(|&&(dx, dy, _)| (dx, dy) == (1, 1))
+            .unwrap();
+        assert!((diagonal.2 - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+        assert_eq!(kernel(Neighborhood::Four, 2).offsets().len(), 12);
+        assert_eq!(kernel(Neighborhood::Eight, 2).offsets().len(), 24);
+
+        let curve = |curve| AdjacencyKernel {
+            curve,
+            delta_e: 10.0,
+            ..Default::default()
+        };
+        assert_eq!(curve(PenaltyCurve::Inverse).penalty(1.0), 0.5);
+        assert_eq!(curve(PenaltyCurve::Exponential).penalty(0.0), 1.0);
+        assert!((curve(PenaltyCurve::Exponential).penalty(10.0) - (-1.0f32).exp()).abs() < 1e-6);
+        assert_eq!(curve(PenaltyCurve::Threshold).penalty(9.9), 1.0);
+        assert_eq!(curve(PenaltyCurve::Threshold).penalty(10.0), 0.0);
+
+        assert_eq!("8".parse::<Neighborhood>().unwrap(), Neighborhood::Eight);
+        assert_eq!(
+            "Threshold".parse::<PenaltyCurve>().unwrap(),
+            PenaltyCurve::Threshold
+        );
+        assert!("6".parse::<Neighborhood>().is_err());
+        assert!("linear".parse::<PenaltyCurve>().is_err());
+    }
+
+    #[test]
+    fn test_penalty_matches_total_cost() {
+        let mut sim_db = SimilarityDatabase::new();
+        for (name, l) in [("a.png", 20.0), ("b.png", 25.0), ("c.png", 60.0)] {
+            sim_db.add_tile(PathBuf::from(name), Lab::new(l, 0.0, 0.0));
+        }
+        sim_db.build_similarities();
+        let kernel = AdjacencyKernel {
+            neighborhood: Neighborhood::Eight,
+            radius: 2,
+            ..Default::default()
+        };
+        let calculator = AdjacencyPenaltyCalculator::new(&sim_db, 1.0).with_kernel(kernel);
+
+        // Placing a tile adds exactly its penalty to the total cost
+        let tile = |name: &str| Some(id(&sim_db, name));
+        let mut grid = TileGrid::from_rows(vec![
+            vec![tile("a.png"), tile("c.png"), None],
+            vec![None, tile("b.png"), tile("a.png")],
+            vec![tile("c.png"), None, tile("b.png")],
+        ]);
+        let before = calculator.calculate_total_cost(&grid);
+        let penalty =
+            calculator.calculate_penalty(id(&sim_db, "b.png"), GridPosition::new(0, 1), &grid);
+        grid.set(0, 1, tile("b.png"));
+        let after = calculator.calculate_total_cost(&grid);
+        assert!((after - before - penalty).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_swap_delta_is_exact_for_every_kernel() {
+        let names: Vec<String> = (0..6).map(|i| format!("tile{i}.png")).collect();
+        let mut sim_db = SimilarityDatabase::new();
+        for (i, name) in names.iter().enumerate() {
+            let l = 20.0 + 4.0 * i as f32;
+            sim_db.add_tile(PathBuf::from(name), Lab::new(l, i as f32, -(i as f32)));
+        }
+        sim_db.build_similarities();
+
+        // Deterministic pseudo-random grid with a few empty cells
+        let mut state = 987654321u64;
+        let mut next = |n: usize| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as usize % n
+        };
+        let (width, height) = (6, 5);
+        let mut grid = TileGrid::from_rows(
+            (0..height)
+                .map(|_| {
+                    (0..width)
+                        .map(|_| {
+                            let pick = next(names.len() + 1);
+                            names.get(pick).map(|name| id(&sim_db, name))
+                        })
+                        .collect()
+                })
+                .collect(),
+        );
+        let weights: Vec<Vec<f32>> = (0..height)
+            .map(|y| (0..width).map(|x| 1.0 + ((x * 3 + y) % 4) as f32).collect())
+            .collect();
+
+        for neighborhood in [Neighborhood::Four, Neighborhood::Eight] {
+            for radius in 1..=3 {
+                for curve in [
+                    PenaltyCurve::Inverse,
+                    PenaltyCurve::Exponential,
+                    PenaltyCurve::Threshold,
+                ] {
+                    let kernel = AdjacencyKernel {
+                        neighborhood,
+                        radius,
+                        curve,
+                        delta_e: 6.0,
+                    };
+                    let calculator = AdjacencyPenaltyCalculator::new(&sim_db, 1.0)
+                        .with_kernel(kernel)
+                        .with_cell_weights(&weights);
+                    for _ in 0..40 {
+                        // Near pairs often fall inside each other's kernel
+                        let pos1 = GridPosition::new(next(width), next(height));
+                        let pos2 = GridPosition::new(
+                            (pos1.x + next(3)).min(width - 1),
+                            (pos1.y + next(3)).min(height - 1),
+                        );
+                        let before = calculator.calculate_total_cost(&grid);
+                        let delta = calculator.calculate_swap_delta(&grid, pos1, pos2);
+                        grid.swap(pos1, pos2);
+                        // Swaps involving an empty cell are not scored
+                        if grid.get(pos1.x, pos1.y).is_none() || grid.get(pos2.x, pos2.y).is_none()
+                        {
+                            continue;
+                        }
+                        let after = calculator.calculate_total_cost(&grid);
+                        assert!(
+                            (after - before - delta).abs() < 1e-3,
+                            "{kernel:?} {pos1:?} {pos2:?}: {delta} vs {}",
+                            after - before
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_change_delta_matches_total_cost() {
+        let names: Vec<String> = (0..5).map(|i| format!("tile{i}.png")).collect();
+        let mut sim_db = SimilarityDatabase::new();
+        for (i, name) in names.iter().enumerate() {
+            sim_db.add_tile(
+                PathBuf::from(name),
+                Lab::new(30.0 + 5.0 * i as f32, 0.0, 0.0),
+            );
+        }
+        sim_db.build_similarities();
+
+        let mut state = 42u64;
+        let mut next = |n: usize| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as usize % n
+        };
+        let (width, height) = (5, 5);
+        let mut grid = TileGrid::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                grid.set(x, y, Some(id(&sim_db, &names[next(names.len())])));
+            }
+        }
+        let calculator =
+            AdjacencyPenaltyCalculator::new(&sim_db, 1.0).with_kernel(AdjacencyKernel {
+                neighborhood: Neighborhood::Eight,
+                radius: 2,
+                ..Default::default()
+            });
+
+        for step in 0..60 {
+            // Alternate three-cell rotations, which often touch each other, and replacements
+            let cells: Vec<GridPosition> = if step % 2 == 0 {
+                let first = GridPosition::new(next(width - 1), next(height - 1));
+                vec![
+                    first,
+                    GridPosition::new(first.x + 1, first.y),
+                    GridPosition::new(first.x, first.y + 1),
+                ]
+            } else {
+                vec![GridPosition::new(next(width), next(height))]
+            };
+            let changes: Vec<(GridPosition, TileId)> = if cells.len() == 3 {
+                (0..3)
+                    .map(|i| {
+                        let from = cells[(i + 2) % 3];
+                        (cells[i], grid.get(from.x, from.y).unwrap())
+                    })
+                    .collect()
+            } else {
+                vec![(cells[0], id(&sim_db, &names[next(names.len())]))]
+            };
+
+            let before = calculator.calculate_total_cost(&grid);
+            let delta = calculator.calculate_change_delta(&grid, &changes);
+            for &(cell, tile) in &changes {
+                grid.set(cell.x, cell.y, Some(tile));
+            }
+            let after = calculator.calculate_total_cost(&grid);
+            assert!(
+                (after - before - delta).abs() < 1e-3,
+                "{changes:?}: {delta} vs {}",
+                after - before
+            );
+        }
+    }
 }
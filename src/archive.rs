@@ -0,0 +1,405 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use zip::ZipArchive;
+
+use crate::limits::ResourceLimits;
+
+/// Index of an opened archive, kept for the lifetime of the process
+///
+/// Only entries with safe relative names are indexed, keyed by their normalized name.
+/// Zip archives map each name to its index and keep a pool of parsed archives so parallel
+/// reads don't wait on each other; tar archives are scanned once and remember where each
+/// entry's data starts, so reading an entry is a single seek.
+enum ArchiveIndex {
+    Zip {
+        entries: HashMap<String, usize>,
+        pool: Mutex<Vec<ZipArchive<File>>>,
+    },
+    Tar(HashMap<String, (u64, u64)>),
+}
+
+static ARCHIVES: OnceLock<Mutex<HashMap<PathBuf, Arc<ArchiveIndex>>>> = OnceLock::new();
+
+/// Check whether a path names a supported archive (`.zip` or `.tar`)
+pub fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| matches!(ext.to_lowercase().as_str(), "zip" | "tar"))
+        .unwrap_or(false)
+}
+
+/// Split a material path such as `pack.zip/photos/a.jpg` into the archive and entry name
+///
+/// Returns `None` for paths that do not point inside an archive on disk.
+pub fn split_entry_path(path: &Path) -> Option<(&Path, String)> {
+    let archive = path
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| is_archive(ancestor) && ancestor.is_file())?;
+    let entry = path
+        .strip_prefix(archive)
+        .ok()?
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    Some((archive, entry))
+}
+
+/// List the files inside an archive as material paths (`archive/entry`), sorted by name
+pub fn list_entries(archive: &Path) -> Result<Vec<PathBuf>> {
+    let index = open_index(archive)?;
+    let mut names: Vec<String> = match index.as_ref() {
+        ArchiveIndex::Zip { entries, .. } => entries.keys().cloned().collect(),
+        ArchiveIndex::Tar(entries) => entries.keys().cloned().collect(),
+    };
+    names.sort();
+    Ok(names.into_iter().map(|name| archive.join(name)).collect())
+}
+
+/// Read a material's bytes, either from a plain file or from inside an archive
+///
+/// Entries larger than the allocation limit are rejected before they are decompressed.
+pub fn read(path: &Path, limits: &ResourceLimits) -> Result<Vec<u8>> {
+    let Some((archive, entry)) = split_entry_path(path) else {
+        let size = std::fs::metadata(path)
+            .with_context(|| format!("Failed to read {path:?}"))?
+            .len();
+        limits.check_allocation(path, size)?;
+        return std::fs::read(path).with_context(|| format!("Failed to read {path:?}"));
+    };
+
+    let index = open_index(archive)?;
+    let mut bytes = Vec::new();
+    match index.as_ref() {
+        ArchiveIndex::Zip { entries, pool } => {
+            let &index = entries
+                .get(&entry)
+                .ok_or_else(|| anyhow!("Entry {entry:?} not found in {archive:?}"))?;
+            let pooled = pool.lock().ok().and_then(|mut pool| pool.pop());
+            let mut zip = match pooled {
+                Some(zip) => zip,
+                None => open_zip(archive)?,
+            };
+            {
+                let file = zip.by_index(index)?;
+                limits.check_allocation(path, file.size())?;
+
+                // The declared size can lie, so never inflate past the limit
+                file.take(limits.max_alloc_bytes + 1)
+                    .read_to_end(&mut bytes)?;
+            }
+            if let Ok(mut pool) = pool.lock() {
+                pool.push(zip);
+            }
+            limits.check_allocation(path, bytes.len() as u64)?;
+        }
+        ArchiveIndex::Tar(entries) => {
+            let &(offset, size) = entries
+                .get(&entry)
+                .ok_or_else(|| anyhow!("Entry {entry:?} not found in {archive:?}"))?;
+            limits.check_allocation(path, size)?;
+            let mut file = File::open(archive)?;
+            file.seek(SeekFrom::Start(offset))?;
+            bytes.resize(size as usize, 0);
+            file.read_exact(&mut bytes)?;
+        }
+    }
+    Ok(bytes)
+}
+
+/// Get the cached index for an archive, opening it on first use
+fn open_index(archive: &Path) -> Result<Arc<ArchiveIndex>> {
+    let cache = ARCHIVES.get_or_init(Default::default);
+    if let Some(index) = cache.lock().ok().and_then(|c| c.get(archive).cloned()) {
+        return Ok(index);
+    }
+
+    let extension = archive
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase());
+    let index = match extension.as_deref() {
+        Some("zip") => {
+            let zip = open_zip(archive)?;
+            let entries = (0..zip.len())
+                .filter_map(|index| {
+                    let name = zip.name_for_index(index)?;
+                    if name.ends_with('/') {
+                        return None;
+                    }
+                    entry_name(archive, Path::new(name)).map(|name| (name, index))
+                })
+                .collect();
+            ArchiveIndex::Zip {
+                entries,
+                pool: Mutex::new(vec![zip]),
+            }
+        }
+        Some("tar") => {
+            let file = File::open(archive)
+                .with_context(|| format!("Failed to open archive {archive:?}"))?;
+            ArchiveIndex::Tar(index_tar(archive, file)?)
+        }
+        _ => bail!("Unsupported archive {archive:?} (expected .zip or .tar)"),
+    };
+
+    let index = Arc::new(index);
+    if let Ok(mut cache) = cache.lock() {
+        cache.insert(archive.to_path_buf(), index.clone());
+    }
+    Ok(index)
+}
+
+/// Open a zip archive and parse its central directory
+fn open_zip(archive: &Path) -> Result<ZipArchive<File>> {
+    let file =
+        File::open(archive).with_context(|| format!("Failed to open archive {archive:?}"))?;
+    ZipArchive::new(file).with_context(|| format!("Invalid zip archive {archive:?}"))
+}
+
+/// Normalize an entry name to `/`-separated components
+///
+/// Names that are absolute or climb out of the archive (`/etc/passwd`, `../a.jpg`) would
+/// turn into paths outside it once joined to the archive path, so they are skipped.
+fn entry_name(archive: &Path, name: &Path) -> Option<String> {
+    let mut parts = Vec::new();
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy()),
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(_) | Component::ParentDir => {
+                eprintln!("Warning: Skipping unsafe entry {name:?} in {archive:?}");
+                return None;
+            }
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+/// Record the data offset and size of every regular file in a tar archive
+fn index_tar(archive: &Path, file: File) -> Result<HashMap<String, (u64, u64)>> {
+    let mut tar = tar::Archive::new(file);
+    let mut entries = HashMap::new();
+    for entry in tar.entries()? {
+        let entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        if let Some(name) = entry_name(archive, &entry.path()?) {
+            entries.insert(name, (entry.raw_file_position(), entry.size()));
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::LimitError;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    fn write_zip(path: &Path, files: &[(&str, &[u8])]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        for (name, data) in files {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    fn write_tar(path: &Path, files: &[(&str, &[u8])]) {
+        let mut tar = tar::Builder::new(File::create(path).unwrap());
+        for (name, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, name, *data).unwrap();
+        }
+        tar.finish().unwrap();
+    }
+
+    #[test]
+    fn test_is_archive() {
+        assert!(is_archive(Path::new("pack.zip")));
+        assert!(is_archive(Path::new("pack.TAR")));
+        assert!(!is_archive(Path::new("pack.tar.gz")));
+        assert!(!is_archive(Path::new("photos")));
+    }
+
+    #[test]
+    fn test_zip_list_and_read() {
+        let dir = tempdir().unwrap();
+        let archive = dir.path().join("pack.zip");
+        write_zip(&archive, &[("b.jpg", b"second"), ("sub/a.png", b"first")]);
+
+        let entries = list_entries(&archive).unwrap();
+
+        assert_eq!(
+            entries,
+            vec![archive.join("b.jpg"), archive.join("sub").join("a.png")]
+        );
+        assert_eq!(
+            read(&entries[1], &ResourceLimits::default()).unwrap(),
+            b"first"
+        );
+        assert!(read(&archive.join("missing.jpg"), &ResourceLimits::default()).is_err());
+    }
+
+    #[test]
+    fn test_tar_list_and_read() {
+        let dir = tempdir().unwrap();
+        let archive = dir.path().join("pack.tar");
+        write_tar(&archive, &[("x/one.png", b"1111"), ("two.png", b"22")]);
+
+        let entries = list_entries(&archive).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            read(&archive.join("two.png"), &ResourceLimits::default()).unwrap(),
+            b"22"
+        );
+        assert_eq!(
+            read(&archive.join("x/one.png"), &ResourceLimits::default()).unwrap(),
+            b"1111"
+        );
+    }
+
+    /// Write a tar with raw entry names, bypassing the builder's path checks
+    fn write_raw_tar(path: &Path, files: &[(&str, &[u8])]) {
+        let mut tar = tar::Builder::new(File::create(path).unwrap());
+        for (name, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_cksum();
+            tar.append(&header, *data).unwrap();
+        }
+        tar.finish().unwrap();
+    }
+
+    #[test]
+    fn test_zip_skips_entries_outside_the_archive() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("secret.png"), b"local").unwrap();
+        let archive = dir.path().join("evil.zip");
+        write_zip(
+            &archive,
+            &[
+                ("../secret.png", b"escape"),
+                ("/etc/passwd", b"absolute"),
+                ("./ok.png", b"fine"),
+            ],
+        );
+
+        let entries = list_entries(&archive).unwrap();
+
+        assert_eq!(entries, vec![archive.join("ok.png")]);
+        assert_eq!(
+            read(&entries[0], &ResourceLimits::default()).unwrap(),
+            b"fine"
+        );
+        assert!(read(&archive.join("../secret.png"), &ResourceLimits::default()).is_err());
+    }
+
+    #[test]
+    fn test_tar_skips_entries_outside_the_archive() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("secret.png"), b"local").unwrap();
+        let archive = dir.path().join("evil.tar");
+        write_raw_tar(
+            &archive,
+            &[
+                ("../secret.png", b"escape"),
+                ("/etc/passwd", b"absolute"),
+                ("sub/../../secret.png", b"nested"),
+                ("ok.png", b"fine"),
+            ],
+        );
+
+        let entries = list_entries(&archive).unwrap();
+
+        assert_eq!(entries, vec![archive.join("ok.png")]);
+        assert!(entries.iter().all(|entry| entry.starts_with(&archive)));
+        assert!(read(&archive.join("../secret.png"), &ResourceLimits::default()).is_err());
+    }
+
+    #[test]
+    fn test_zip_reads_in_parallel() {
+        let dir = tempdir().unwrap();
+        let archive = dir.path().join("pack.zip");
+        let names: Vec<String> = (0..16).map(|i| format!("{i}.png")).collect();
+        let files: Vec<(&str, &[u8])> = names
+            .iter()
+            .map(|name| (name.as_str(), name.as_bytes()))
+            .collect();
+        write_zip(&archive, &files);
+
+        std::thread::scope(|scope| {
+            for name in &names {
+                let path = archive.join(name);
+                scope.spawn(move || {
+                    assert_eq!(
+                        read(&path, &ResourceLimits::default()).unwrap(),
+                        name.as_bytes()
+                    );
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn test_split_entry_path() {
+        let dir = tempdir().unwrap();
+        let archive = dir.path().join("pack.zip");
+        write_zip(&archive, &[("a.jpg", b"")]);
+
+        let path = archive.join("nested/a.jpg");
+        let (found, entry) = split_entry_path(&path).unwrap();
+        assert_eq!(found, archive.as_path());
+        assert_eq!(entry, "nested/a.jpg");
+
+        // Plain files and archives that don't exist are not split
+        assert!(split_entry_path(&dir.path().join("a.jpg")).is_none());
+        assert!(split_entry_path(&dir.path().join("other.zip/a.jpg")).is_none());
+    }
+
+    #[test]
+    fn test_read_rejects_entries_over_allocation_limit() {
+        let dir = tempdir().unwrap();
+        let archive = dir.path().join("bomb.zip");
+        write_zip(&archive, &[("big.png", &[0u8; 4096])]);
+        let limits = ResourceLimits {
+            max_alloc_bytes: 1024,
+            ..Default::default()
+        };
+
+        let err = read(&archive.join("big.png"), &limits).unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<LimitError>(),
+            Some(LimitError::Allocation {
+                requested: Some(4096),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_read_plain_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("plain.png");
+        std::fs::write(&path, b"data").unwrap();
+
+        assert_eq!(read(&path, &ResourceLimits::default()).unwrap(), b"data");
+    }
+}
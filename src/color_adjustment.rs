@@ -1,6 +1,8 @@
 use image::{DynamicImage, ImageBuffer, Rgb};
 use palette::{Hsv, IntoColor, Srgb};
 
+use crate::linear_light;
+
 /// Color adjustments to apply to tiles for better matching
 #[derive(Debug, Clone, Copy)]
 pub struct ColorAdjustment {
@@ -49,6 +51,34 @@ impl ColorAdjustment {
         DynamicImage::ImageRgb8(adjusted_buffer)
     }
 
+    /// Apply adjustments with brightness and contrast computed in linear light
+    pub fn apply_to_image_linear(&self, img: &DynamicImage) -> DynamicImage {
+        let rgb_img = img.to_rgb8();
+        let (width, height) = rgb_img.dimensions();
+
+        let adjusted_buffer = ImageBuffer::from_fn(width, height, |x, y| {
+            self.adjust_pixel_linear(*rgb_img.get_pixel(x, y))
+        });
+
+        DynamicImage::ImageRgb8(adjusted_buffer)
+    }
+
+    /// Adjust a single pixel, applying brightness and contrast to linear-light values
+    ///
+    /// Contrast pivots on the linear value of sRGB mid-grey so a neutral adjustment and
+    /// [`Self::adjust_pixel`] agree on what "middle" means.
+    pub fn adjust_pixel_linear(&self, pixel: Rgb<u8>) -> Rgb<u8> {
+        let pivot = linear_light::to_linear(128);
+        let adjusted = pixel.0.map(|c| {
+            let linear = linear_light::to_linear(c);
+            let contrasted = ((linear - pivot) * self.contrast + pivot).clamp(0.0, 1.0);
+            let brightened = (contrasted + self.brightness).clamp(0.0, 1.0);
+            linear_light::to_srgb(brightened) as f32 / 255.0
+        });
+
+        self.finish_pixel(Srgb::new(adjusted[0], adjusted[1], adjusted[2]))
+    }
+
     /// Adjust a single pixel
     pub fn adjust_pixel(&self, pixel: Rgb<u8>) -> Rgb<u8> {
         // Convert to float RGB
@@ -65,6 +95,11 @@ impl ColorAdjustment {
             apply_brightness_contrast(srgb.blue, self.brightness, self.contrast),
         );
 
+        self.finish_pixel(brightness_adjusted)
+    }
+
+    /// Apply hue and saturation to a brightness-adjusted color and convert it back to bytes
+    fn finish_pixel(&self, brightness_adjusted: Srgb) -> Rgb<u8> {
         // Apply hue and saturation adjustments if needed
         let final_color = if self.hue_shift != 0.0 || self.saturation != 1.0 {
             let hsv: Hsv = brightness_adjusted.into_color();
@@ -115,9 +150,42 @@ pub fn calculate_optimal_adjustment(
     let target_luma = 0.299 * target_r + 0.587 * target_g + 0.114 * target_b;
     let brightness_diff = (target_luma - tile_luma) * adjustment_strength;
 
-    // Convert to HSV to analyze hue and saturation differences
     let tile_srgb = Srgb::new(tile_r, tile_g, tile_b);
     let target_srgb = Srgb::new(target_r, target_g, target_b);
+    adjustment_towards(tile_srgb, target_srgb, brightness_diff, adjustment_strength)
+}
+
+/// Calculate the adjustment for [`ColorAdjustment::apply_to_image_linear`]
+///
+/// The brightness difference is measured as linear relative luminance, which is what the
+/// linear adjustment adds to each channel.
+pub fn calculate_optimal_adjustment_linear(
+    tile_avg_rgb: Rgb<u8>,
+    target_avg_rgb: Rgb<u8>,
+    adjustment_strength: f32,
+) -> ColorAdjustment {
+    let tile_luma = linear_light::luminance(tile_avg_rgb.0.map(linear_light::to_linear));
+    let target_luma = linear_light::luminance(target_avg_rgb.0.map(linear_light::to_linear));
+    let brightness_diff = (target_luma - tile_luma) * adjustment_strength;
+
+    let [tile_r, tile_g, tile_b] = tile_avg_rgb.0.map(|c| c as f32 / 255.0);
+    let [target_r, target_g, target_b] = target_avg_rgb.0.map(|c| c as f32 / 255.0);
+    adjustment_towards(
+        Srgb::new(tile_r, tile_g, tile_b),
+        Srgb::new(target_r, target_g, target_b),
+        brightness_diff,
+        adjustment_strength,
+    )
+}
+
+/// Combine a brightness change with the hue and saturation changes between two colors
+fn adjustment_towards(
+    tile_srgb: Srgb,
+    target_srgb: Srgb,
+    brightness_diff: f32,
+    adjustment_strength: f32,
+) -> ColorAdjustment {
+    // Convert to HSV to analyze hue and saturation differences
 
     let tile_hsv: Hsv = tile_srgb.into_color();
     let target_hsv: Hsv = target_srgb.into_color();
@@ -229,6 +297,23 @@ mod tests {
         assert!((adjustment.saturation - 1.0).abs() < 0.01);
     }
 
+    #[test]
+    fn test_linear_adjustment_identity_and_brightness() {
+        let neutral = ColorAdjustment::default();
+        for value in [0u8, 40, 128, 220, 255] {
+            let pixel = Rgb([value, value, value]);
+            assert_eq!(neutral.adjust_pixel_linear(pixel), pixel);
+        }
+
+        // Matching a dark tile to a lighter target brightens it in linear light
+        let dark = Rgb([60, 60, 60]);
+        let light = Rgb([180, 180, 180]);
+        let adjustment = calculate_optimal_adjustment_linear(dark, light, 1.0);
+        let adjusted = adjustment.adjust_pixel_linear(dark);
+        assert!(adjustment.brightness > 0.0);
+        assert_eq!(adjusted, light);
+    }
+
     #[test]
     fn test_apply_brightness_contrast() {
         // Test midtone with no adjustment
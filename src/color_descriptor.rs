@@ -0,0 +1,386 @@
+use anyhow::{bail, Result};
+use image::DynamicImage;
+use palette::{FromColor, Lab, Srgb};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{MosaicGenerator, MosaicGeneratorImpl};
+
+/// Default fraction trimmed from each end by [`ColorDescriptor::TrimmedMean`]
+pub const DEFAULT_TRIM_FRACTION: f32 = 0.1;
+/// Default number of k-means clusters for [`ColorDescriptor::Dominant`]
+pub const DEFAULT_DOMINANT_CLUSTERS: usize = 4;
+
+const KMEANS_ITERATIONS: usize = 10;
+
+/// Statistic used to summarize an image (a material or a target cell) as one Lab color
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorDescriptor {
+    /// Arithmetic mean
+    #[default]
+    Mean,
+    /// Per-channel median
+    Median,
+    /// Per-channel mean after dropping a fraction of the lowest and highest values
+    TrimmedMean,
+    /// Centroid of the largest k-means cluster
+    Dominant,
+}
+
+impl FromStr for ColorDescriptor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "mean" => Ok(Self::Mean),
+            "median" => Ok(Self::Median),
+            "trimmed" | "trimmed-mean" => Ok(Self::TrimmedMean),
+            "dominant" => Ok(Self::Dominant),
+            _ => {
+                bail!("Unknown color descriptor {s:?} (expected mean, median, trimmed or dominant)")
+            }
+        }
+    }
+}
+
+impl fmt::Display for ColorDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Mean => "mean",
+            Self::Median => "median",
+            Self::TrimmedMean => "trimmed",
+            Self::Dominant => "dominant",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A descriptor together with its parameters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DescriptorConfig {
+    pub descriptor: ColorDescriptor,
+    /// Fraction (0.0-0.5) dropped from each end of every channel for the trimmed mean
+    pub trim_fraction: f32,
+    /// Number of clusters for the dominant color
+    pub clusters: usize,
+    /// How strongly pixels are weighted toward the image center (0.0 = uniform, 1.0 =
+    /// corners ignored); weights fall off linearly with distance from the center
+    pub center_weight: f32,
+}
+
+impl Default for DescriptorConfig {
+    fn default() -> Self {
+        Self {
+            descriptor: ColorDescriptor::Mean,
+            trim_fraction: DEFAULT_TRIM_FRACTION,
+            clusters: DEFAULT_DOMINANT_CLUSTERS,
+            center_weight: 0.0,
+        }
+    }
+}
+
+/// Color summary of an image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorSummary {
+    pub lab: Lab,
+    /// Share of the (weighted) pixels the color represents; below 1.0 only for the
+    /// dominant color, where it is the size of the winning cluster
+    pub coverage: f32,
+}
+
+impl DescriptorConfig {
+    /// Summarize `img` as a single Lab color
+    pub fn describe(&self, img: &DynamicImage) -> ColorSummary {
+        // Keep the plain mean bit-for-bit identical to the original calculation
+        if self.descriptor == ColorDescriptor::Mean && self.center_weight <= 0.0 {
+            return ColorSummary {
+                lab: MosaicGeneratorImpl::calculate_average_lab(img),
+                coverage: 1.0,
+            };
+        }
+
+        let samples = self.weighted_pixels(img);
+        if samples.is_empty() {
+            return ColorSummary {
+                lab: Lab::new(0.0, 0.0, 0.0),
+                coverage: 0.0,
+            };
+        }
+
+        let lab = match self.descriptor {
+            ColorDescriptor::Mean => weighted_mean(&samples),
+            ColorDescriptor::Median => {
+                let [l, a, b] = [0, 1, 2].map(|c| weighted_trimmed_mean(&samples, c, 0.5));
+                Lab::new(l, a, b)
+            }
+            ColorDescriptor::TrimmedMean => {
+                let trim = self.trim_fraction.clamp(0.0, 0.5);
+                let [l, a, b] = [0, 1, 2].map(|c| weighted_trimmed_mean(&samples, c, trim));
+                Lab::new(l, a, b)
+            }
+            ColorDescriptor::Dominant => return dominant_color(&samples, self.clusters),
+        };
+        ColorSummary { lab, coverage: 1.0 }
+    }
+
+    /// Convert pixels to Lab weighted by opacity and distance from the center, dropping
+    /// pixels with zero weight
+    fn weighted_pixels(&self, img: &DynamicImage) -> Vec<([f32; 3], f32)> {
+        let rgb = img.to_rgba8();
+        let (width, height) = rgb.dimensions();
+        let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+        let max_distance = (cx * cx + cy * cy).sqrt().max(f32::EPSILON);
+        let strength = self.center_weight.clamp(0.0, 1.0);
+
+        rgb.enumerate_pixels()
+            .filter_map(|(x, y, pixel)| {
+                let dx = x as f32 + 0.5 - cx;
+                let dy = y as f32 + 0.5 - cy;
+                let weight = (1.0 - strength * (dx * dx + dy * dy).sqrt() / max_distance)
+                    * pixel[3] as f32
+                    / 255.0;
+                if weight <= 0.0 {
+                    return None;
+                }
+                let lab = Lab::from_color(Srgb::new(pixel[0], pixel[1], pixel[2]).into_format());
+                Some(([lab.l, lab.a, lab.b], weight))
+            })
+            .collect()
+    }
+}
+
+fn weighted_mean(samples: &[([f32; 3], f32)]) -> Lab {
+    let total: f32 = samples.iter().map(|(_, w)| w).sum();
+    let mut sum = [0.0f32; 3];
+    for (color, weight) in samples {
+        for c in 0..3 {
+            sum[c] += color[c] * weight;
+        }
+    }
+    Lab::new(sum[0] / total, sum[1] / total, sum[2] / total)
+}
+
+/// Weighted mean of one channel over the middle `1 - 2 * trim` of the weight
+///
+/// A trim of 0.5 leaves an infinitely thin middle, which is the weighted median.
+fn weighted_trimmed_mean(samples: &[([f32; 3], f32)], channel: usize, trim: f32) -> f32 {
+    let mut values: Vec<(f32, f32)> = samples.iter().map(|(c, w)| (c[channel], *w)).collect();
+    values.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let total: f32 = values.iter().map(|(_, w)| w).sum();
+
+    if trim >= 0.5 {
+        let half = total / 2.0;
+        let mut cumulative = 0.0;
+        for (value, weight) in &values {
+            cumulative += weight;
+            if cumulative >= half {
+                return *value;
+            }
+        }
+        return values.last().map(|(v, _)| *v).unwrap_or(0.0);
+    }
+
+    // Keep the part of each sample's weight that falls inside [low, high]
+    let (low, high) = (total * trim, total * (1.0 - trim));
+    let mut start = 0.0;
+    let (mut sum, mut kept) = (0.0, 0.0);
+    for (value, weight) in values {
+        let end = start + weight;
+        let inside = (end.min(high) - start.max(low)).max(0.0);
+        sum += value * inside;
+        kept += inside;
+        start = end;
+    }
+    if kept > 0.0 {
+        sum / kept
+    } else {
+        0.0
+    }
+}
+
+/// Weighted k-means in Lab, returning the centroid of the heaviest cluster
+///
+/// Centroids start at lightness quantiles, so results are deterministic.
+fn dominant_color(samples: &[([f32; 3], f32)], clusters: usize) -> ColorSummary {
+    let mut by_lightness: Vec<usize> = (0..samples.len()).collect();
+    by_lightness.sort_by(|&i, &j| samples[i].0[0].total_cmp(&samples[j].0[0]));
+    let k = clusters.clamp(1, samples.len());
+    let mut centroids: Vec<[f32; 3]> = (0..k)
+        .map(|i| samples[by_lightness[(2 * i + 1) * samples.len() / (2 * k)]].0)
+        .collect();
+
+    let nearest = |color: &[f32; 3], centroids: &[[f32; 3]]| -> usize {
+        let distance = |c: &[f32; 3]| (0..3).map(|ch| (color[ch] - c[ch]).powi(2)).sum::<f32>();
+        (0..centroids.len())
+            .min_by(|&a, &b| distance(&centroids[a]).total_cmp(&distance(&centroids[b])))
+            .unwrap_or(0)
+    };
+
+    let mut weights = vec![0.0f32; k];
+    for _ in 0..KMEANS_ITERATIONS {
+        let mut sums = vec![[0.0f32; 3]; k];
+        weights = vec![0.0; k];
+        for (color, weight) in samples {
+            let cluster = nearest(color, &centroids);
+            for c in 0..3 {
+                sums[cluster][c] += color[c] * weight;
+            }
+            weights[cluster] += weight;
+        }
+
+        let mut moved = false;
+        for cluster in 0..k {
+            if weights[cluster] > 0.0 {
+                let updated = sums[cluster].map(|s| s / weights[cluster]);
+                moved |= updated != centroids[cluster];
+                centroids[cluster] = updated;
+            }
+        }
+        if !moved {
+            break;
+        }
+    }
+
+    let total: f32 = weights.iter().sum();
+    let (best, weight) = weights
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(idx, w)| (idx, *w))
+        .unwrap_or((0, 0.0));
+    let [l, a, b] = centroids[best];
+    ColorSummary {
+        lab: Lab::new(l, a, b),
+        coverage: if total > 0.0 { weight / total } else { 0.0 },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    /// 10x10 image: white top `white_rows` rows, near-black below
+    fn sky_and_ground(white_rows: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(10, 10, |_, y| {
+            if y < white_rows {
+                Rgb([255, 255, 255])
+            } else {
+                Rgb([20, 20, 20])
+            }
+        }))
+    }
+
+    fn config(descriptor: ColorDescriptor) -> DescriptorConfig {
+        DescriptorConfig {
+            descriptor,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_descriptor() {
+        assert_eq!(
+            "dominant".parse::<ColorDescriptor>().unwrap(),
+            ColorDescriptor::Dominant
+        );
+        assert_eq!(
+            "Trimmed-Mean".parse::<ColorDescriptor>().unwrap(),
+            ColorDescriptor::TrimmedMean
+        );
+        assert!("mode".parse::<ColorDescriptor>().is_err());
+    }
+
+    #[test]
+    fn test_mean_matches_average_lab() {
+        let img = sky_and_ground(3);
+        let summary = config(ColorDescriptor::Mean).describe(&img);
+        assert_eq!(
+            summary.lab,
+            MosaicGeneratorImpl::calculate_average_lab(&img)
+        );
+        assert_eq!(summary.coverage, 1.0);
+    }
+
+    #[test]
+    fn test_median_picks_a_color_that_occurs() {
+        let img = sky_and_ground(4);
+
+        let mean = config(ColorDescriptor::Mean).describe(&img).lab;
+        let median = config(ColorDescriptor::Median).describe(&img).lab;
+
+        // The mean is a grey in between; the median is the dark ground
+        assert!(mean.l > 30.0 && mean.l < 70.0);
+        assert!(median.l < 10.0);
+    }
+
+    #[test]
+    fn test_trimmed_mean_ignores_outliers() {
+        // One white pixel in a dark image
+        let mut img = RgbImage::from_pixel(10, 10, Rgb([20, 20, 20]));
+        img.put_pixel(0, 0, Rgb([255, 255, 255]));
+        let img = DynamicImage::ImageRgb8(img);
+
+        let mean = config(ColorDescriptor::Mean).describe(&img).lab;
+        let trimmed = config(ColorDescriptor::TrimmedMean).describe(&img).lab;
+        let dark = config(ColorDescriptor::Mean)
+            .describe(&DynamicImage::ImageRgb8(RgbImage::from_pixel(
+                1,
+                1,
+                Rgb([20, 20, 20]),
+            )))
+            .lab;
+
+        assert!((trimmed.l - dark.l).abs() < 0.01);
+        assert!(mean.l > trimmed.l + 0.5);
+    }
+
+    #[test]
+    fn test_dominant_color_and_coverage() {
+        let img = sky_and_ground(3);
+
+        let summary = config(ColorDescriptor::Dominant).describe(&img);
+
+        assert!(summary.lab.l < 10.0);
+        assert!((summary.coverage - 0.7).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_transparent_pixels_are_ignored() {
+        // Mostly transparent black around a few opaque white pixels
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::from_fn(10, 10, |x, _| {
+            if x < 2 {
+                image::Rgba([255, 255, 255, 255])
+            } else {
+                image::Rgba([0, 0, 0, 0])
+            }
+        }));
+
+        for descriptor in [ColorDescriptor::Median, ColorDescriptor::Dominant] {
+            let summary = config(descriptor).describe(&img);
+            assert!((summary.lab.l - 100.0).abs() < 0.5, "{descriptor}");
+        }
+    }
+
+    #[test]
+    fn test_center_weight_favors_middle() {
+        // White border around a dark 4x4 center
+        let img = DynamicImage::ImageRgb8(RgbImage::from_fn(10, 10, |x, y| {
+            if (3..7).contains(&x) && (3..7).contains(&y) {
+                Rgb([20, 20, 20])
+            } else {
+                Rgb([255, 255, 255])
+            }
+        }));
+
+        let uniform = config(ColorDescriptor::Mean).describe(&img).lab;
+        let centered = DescriptorConfig {
+            center_weight: 1.0,
+            ..config(ColorDescriptor::Mean)
+        }
+        .describe(&img)
+        .lab;
+
+        assert!(centered.l < uniform.l - 5.0);
+    }
+}
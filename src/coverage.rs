@@ -0,0 +1,210 @@
+use anyhow::{bail, Result};
+
+/// Regret of a material in a cell it may not use, larger than any sum of real regrets
+const INFEASIBLE: f64 = 1.0e12;
+
+/// Give every material its own cell, minimizing the total color error this adds
+///
+/// `distances[material][cell]` is the color distance of a material to a cell, or infinity
+/// where the material may not go. Rather than the raw distance, each placement costs its
+/// regret: how much worse the material matches than the best material for that cell. Cells
+/// the unconstrained matcher would fill just as well are then preferred, and total error
+/// stays as low as the guarantee allows.
+///
+/// Returns the cell of each material. Fails when there are fewer cells than materials or
+/// when the allowed cells can't hold every material. Memory grows with materials x cells
+/// and time with materials² x cells.
+pub fn assign_materials(distances: &[Vec<f32>]) -> Result<Vec<usize>> {
+    let materials = distances.len();
+    let cells = distances.first().map_or(0, Vec::len);
+    if materials > cells {
+        bail!(
+            "Cannot use all {materials} materials on a grid of {cells} cells; enlarge the grid or lower --max-materials"
+        );
+    }
+
+    let mut best = vec![f32::INFINITY; cells];
+    for row in distances {
+        for (best, &distance) in best.iter_mut().zip(row) {
+            *best = best.min(distance);
+        }
+    }
+    let regret: Vec<Vec<f64>> = distances
+        .iter()
+        .map(|row| {
+            row.iter()
+                .zip(&best)
+                .map(|(&distance, &best)| {
+                    if distance.is_finite() {
+                        (distance - best) as f64
+                    } else {
+                        INFEASIBLE
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    let assignment = hungarian(&regret);
+    let stranded = assignment
+        .iter()
+        .enumerate()
+        .filter(|&(material, &cell)| regret[material][cell] >= INFEASIBLE)
+        .count();
+    if stranded > 0 {
+        bail!(
+            "Cannot place all {materials} materials: {stranded} have no free cell in the regions they may use"
+        );
+    }
+    Ok(assignment)
+}
+
+/// Minimum-cost assignment of each row to a distinct column, for at most as many rows as
+/// columns
+///
+/// Shortest augmenting paths with potentials; O(rows² · columns).
+fn hungarian(cost: &[Vec<f64>]) -> Vec<usize> {
+    let rows = cost.len();
+    let columns = cost.first().map_or(0, Vec::len);
+    // 1-based, with row and column 0 as the sentinel the augmenting paths start from
+    let mut u = vec![0.0f64; rows + 1];
+    let mut v = vec![0.0f64; columns + 1];
+    let mut row_of = vec![0usize; columns + 1];
+    let mut previous = vec![0usize; columns + 1];
+
+    for row in 1..=rows {
+        row_of[0] = row;
+        let mut column = 0;
+        let mut min_slack = vec![f64::INFINITY; columns + 1];
+        let mut visited = vec![false; columns + 1];
+
+        // Grow a tree of tight edges until it reaches a free column
+        loop {
+            visited[column] = true;
+            let current_row = row_of[column];
+            let mut delta = f64::INFINITY;
+            let mut next = 0;
+            for j in 1..=columns {
+                if visited[j] {
+                    continue;
+                }
+                let slack = cost[current_row - 1][j - 1] - u[current_row] - v[j];
+                if slack < min_slack[j] {
+                    min_slack[j] = slack;
+                    previous[j] = column;
+                }
+                if min_slack[j] < delta {
+                    delta = min_slack[j];
+                    next = j;
+                }
+            }
+            for j in 0..=columns {
+                if visited[j] {
+                    u[row_of[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min_slack[j] -= delta;
+                }
+            }
+            column = next;
+            if row_of[column] == 0 {
+                break;
+            }
+        }
+
+        // Flip the assignments along the augmenting path
+        while column != 0 {
+            let parent = previous[column];
+            row_of[column] = row_of[parent];
+            column = parent;
+        }
+    }
+
+    let mut assignment = vec![0; rows];
+    for (column, &row) in row_of.iter().enumerate().skip(1) {
+        if row != 0 {
+            assignment[row - 1] = column - 1;
+        }
+    }
+    assignment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cheapest assignment by trying every injective mapping
+    fn brute_force(cost: &[Vec<f64>]) -> f64 {
+        fn search(cost: &[Vec<f64>], row: usize, taken: &mut Vec<bool>) -> f64 {
+            if row == cost.len() {
+                return 0.0;
+            }
+            let mut best = f64::INFINITY;
+            for column in 0..taken.len() {
+                if !taken[column] {
+                    taken[column] = true;
+                    best = best.min(cost[row][column] + search(cost, row + 1, taken));
+                    taken[column] = false;
+                }
+            }
+            best
+        }
+        search(cost, 0, &mut vec![false; cost[0].len()])
+    }
+
+    #[test]
+    fn test_hungarian_is_optimal() {
+        // Deterministic pseudo-random matrices of a few shapes
+        let mut state = 12345u64;
+        let mut next = || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 40) as f64 / (1u64 << 24) as f64 * 100.0
+        };
+        for (rows, columns) in [(1, 1), (3, 3), (3, 5), (5, 6), (6, 6)] {
+            let cost: Vec<Vec<f64>> = (0..rows)
+                .map(|_| (0..columns).map(|_| next()).collect())
+                .collect();
+            let assignment = hungarian(&cost);
+
+            let mut columns_used = assignment.clone();
+            columns_used.sort_unstable();
+            columns_used.dedup();
+            assert_eq!(columns_used.len(), rows);
+
+            let total: f64 = assignment
+                .iter()
+                .enumerate()
+                .map(|(r, &c)| cost[r][c])
+                .sum();
+            assert!(
+                (total - brute_force(&cost)).abs() < 1e-9,
+                "{rows}x{columns}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_materials_go_where_they_cost_least() {
+        // Material 0 is the best match everywhere; material 1 is only bad in cell 0
+        let distances = vec![vec![0.0, 0.0, 0.0], vec![50.0, 5.0, 10.0]];
+        assert_eq!(assign_materials(&distances).unwrap()[1], 1);
+
+        assert!(assign_materials(&[vec![1.0], vec![2.0]]).is_err());
+        assert!(assign_materials(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_infeasible_cells_are_avoided() {
+        let inf = f32::INFINITY;
+        // Material 1 matches cell 0 best but may only use cell 2
+        let distances = vec![vec![0.0, 5.0, 5.0], vec![inf, inf, 30.0]];
+        assert_eq!(assign_materials(&distances).unwrap(), vec![0, 2]);
+
+        // Both materials only fit cell 0
+        let distances = vec![vec![0.0, inf], vec![1.0, inf]];
+        let error = assign_materials(&distances).unwrap_err();
+        assert!(error.to_string().contains("1 have no free cell"), "{error}");
+    }
+}
@@ -0,0 +1,114 @@
+use palette::Lab;
+
+/// Floyd–Steinberg weights as (forward offset, row offset, weight); "forward" follows the
+/// direction the current row is traversed in
+const KERNEL: [(isize, usize, f32); 4] = [
+    (1, 0, 7.0 / 16.0),
+    (-1, 1, 3.0 / 16.0),
+    (0, 1, 5.0 / 16.0),
+    (1, 1, 1.0 / 16.0),
+];
+
+/// Floyd–Steinberg error diffusion in Lab over the cells of a grid
+///
+/// Cells must be visited in serpentine order: even rows left to right, odd rows right to
+/// left. The part of each cell's color that its tile misses is carried to the cells not
+/// yet matched, so a library without intermediate colors mixes neighbors to approximate
+/// them instead of banding.
+#[derive(Debug, Clone)]
+pub struct ErrorDiffusion {
+    width: usize,
+    height: usize,
+    strength: f32,
+    error: Vec<[f32; 3]>,
+}
+
+impl ErrorDiffusion {
+    /// `strength` scales the carried error: 0.0 disables diffusion, 1.0 is classic
+    /// Floyd–Steinberg
+    pub fn new(width: usize, height: usize, strength: f32) -> Self {
+        Self {
+            width,
+            height,
+            strength: strength.clamp(0.0, 1.0),
+            error: vec![[0.0; 3]; width * height],
+        }
+    }
+
+    /// The color to match at `(x, y)`: the cell's own color plus the error carried into it
+    pub fn target(&self, x: usize, y: usize, cell_lab: Lab) -> Lab {
+        let [l, a, b] = self.error[y * self.width + x];
+        // Keep runaway error inside the gamut so it can't push matches to absurd colors
+        Lab::new(
+            (cell_lab.l + l).clamp(0.0, 100.0),
+            (cell_lab.a + a).clamp(-128.0, 127.0),
+            (cell_lab.b + b).clamp(-128.0, 127.0),
+        )
+    }
+
+    /// Spread the difference between the color wanted at `(x, y)` (from [`Self::target`])
+    /// and the color of the tile placed there onto the cells that come later
+    pub fn diffuse(&mut self, x: usize, y: usize, wanted: Lab, placed: Lab) {
+        let miss = [
+            (wanted.l - placed.l) * self.strength,
+            (wanted.a - placed.a) * self.strength,
+            (wanted.b - placed.b) * self.strength,
+        ];
+        let forward: isize = if y.is_multiple_of(2) { 1 } else { -1 };
+
+        for (dx, dy, weight) in KERNEL {
+            let nx = x as isize + dx * forward;
+            let ny = y + dy;
+            if nx < 0 || nx as usize >= self.width || ny >= self.height {
+                continue;
+            }
+            let cell = &mut self.error[ny * self.width + nx as usize];
+            for c in 0..3 {
+                cell[c] += miss[c] * weight;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_at(diffusion: &ErrorDiffusion, x: usize, y: usize) -> f32 {
+        diffusion.target(x, y, Lab::new(50.0, 0.0, 0.0)).l - 50.0
+    }
+
+    #[test]
+    fn test_diffuse_follows_row_direction() {
+        let wanted = Lab::new(60.0, 0.0, 0.0);
+        let placed = Lab::new(44.0, 0.0, 0.0);
+
+        // Even rows push forward to the right
+        let mut diffusion = ErrorDiffusion::new(3, 3, 1.0);
+        diffusion.diffuse(1, 0, wanted, placed);
+        assert_eq!(error_at(&diffusion, 2, 0), 7.0);
+        assert_eq!(error_at(&diffusion, 0, 1), 3.0);
+        assert_eq!(error_at(&diffusion, 1, 1), 5.0);
+        assert_eq!(error_at(&diffusion, 2, 1), 1.0);
+        assert_eq!(error_at(&diffusion, 0, 0), 0.0);
+
+        // Odd rows are traversed right to left, so the kernel is mirrored
+        let mut diffusion = ErrorDiffusion::new(3, 3, 0.5);
+        diffusion.diffuse(1, 1, wanted, placed);
+        assert_eq!(error_at(&diffusion, 0, 1), 3.5);
+        assert_eq!(error_at(&diffusion, 2, 2), 1.5);
+        assert_eq!(error_at(&diffusion, 0, 2), 0.5);
+        assert_eq!(error_at(&diffusion, 2, 1), 0.0);
+    }
+
+    #[test]
+    fn test_error_is_dropped_at_edges_and_clamped() {
+        let mut diffusion = ErrorDiffusion::new(2, 1, 1.0);
+        diffusion.diffuse(1, 0, Lab::new(90.0, 0.0, 0.0), Lab::new(10.0, 0.0, 0.0));
+        assert_eq!(error_at(&diffusion, 0, 0), 0.0);
+
+        diffusion.diffuse(0, 0, Lab::new(100.0, 0.0, 0.0), Lab::new(0.0, 0.0, 0.0));
+        let target = diffusion.target(1, 0, Lab::new(80.0, 0.0, 0.0));
+        assert_eq!(target.l, 100.0);
+    }
+}
@@ -0,0 +1,399 @@
+use anyhow::{bail, Result};
+use image::{DynamicImage, GenericImageView, Rgb, RgbImage, Rgba, RgbaImage};
+use std::fmt;
+use std::str::FromStr;
+
+/// How a material is fitted into a grid cell whose aspect ratio differs from its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FitMode {
+    /// Scale to the cell, distorting the material
+    #[default]
+    Stretch,
+    /// Crop the largest centered window with the cell's aspect ratio
+    Cover,
+    /// Show the whole material, padding the remaining space
+    Contain,
+    /// Crop the window with the most detail (or around the focus point)
+    Smart,
+}
+
+impl FromStr for FitMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "stretch" => Ok(Self::Stretch),
+            "cover" | "crop" => Ok(Self::Cover),
+            "contain" | "letterbox" => Ok(Self::Contain),
+            "smart" => Ok(Self::Smart),
+            _ => bail!("Unknown fit mode {s:?} (expected stretch, cover, contain or smart)"),
+        }
+    }
+}
+
+impl fmt::Display for FitMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Stretch => "stretch",
+            Self::Cover => "cover",
+            Self::Contain => "contain",
+            Self::Smart => "smart",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A crop window in normalized coordinates (0.0-1.0), independent of resolution
+///
+/// Crops are chosen on the analysis proxy and applied unchanged to the full image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CropRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl CropRect {
+    pub const FULL: CropRect = CropRect {
+        x: 0.0,
+        y: 0.0,
+        width: 1.0,
+        height: 1.0,
+    };
+
+    /// Convert to a pixel rectangle `(x, y, width, height)` inside a `width` x `height` image
+    pub fn to_pixels(&self, width: u32, height: u32) -> (u32, u32, u32, u32) {
+        let x = ((self.x * width as f32).round() as u32).min(width.saturating_sub(1));
+        let y = ((self.y * height as f32).round() as u32).min(height.saturating_sub(1));
+        let w = ((self.width * width as f32).round() as u32).clamp(1, width - x);
+        let h = ((self.height * height as f32).round() as u32).clamp(1, height - y);
+        (x, y, w, h)
+    }
+}
+
+/// Largest window with aspect ratio `aspect` inside a `width` x `height` image
+///
+/// The window is centered on `focus` (normalized, default the image center) as far as
+/// the image bounds allow.
+pub fn cover_crop(width: u32, height: u32, aspect: f32, focus: Option<(f32, f32)>) -> CropRect {
+    let src_aspect = width as f32 / height as f32;
+    let (crop_w, crop_h) = if src_aspect > aspect {
+        (aspect / src_aspect, 1.0)
+    } else {
+        (1.0, src_aspect / aspect)
+    };
+    let (fx, fy) = focus.unwrap_or((0.5, 0.5));
+    CropRect {
+        x: (fx - crop_w / 2.0).clamp(0.0, 1.0 - crop_w),
+        y: (fy - crop_h / 2.0).clamp(0.0, 1.0 - crop_h),
+        width: crop_w,
+        height: crop_h,
+    }
+}
+
+/// Cover crop positioned on the most salient part of the image
+///
+/// Saliency is the luma gradient magnitude; the window slides along the axis that has
+/// to be cropped and keeps the position with the largest total.
+pub fn smart_crop(img: &DynamicImage, aspect: f32) -> CropRect {
+    let (width, height) = img.dimensions();
+    let centered = cover_crop(width, height, aspect, None);
+    if width < 3 || height < 3 {
+        return centered;
+    }
+
+    let luma = img.to_luma8();
+    let horizontal = centered.width < 1.0;
+    let mut profile = vec![0.0f64; if horizontal { width } else { height } as usize];
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let dx = luma.get_pixel(x + 1, y)[0] as f64 - luma.get_pixel(x - 1, y)[0] as f64;
+            let dy = luma.get_pixel(x, y + 1)[0] as f64 - luma.get_pixel(x, y - 1)[0] as f64;
+            let idx = if horizontal { x } else { y } as usize;
+            profile[idx] += (dx * dx + dy * dy).sqrt();
+        }
+    }
+
+    let len = profile.len();
+    let window = if horizontal {
+        centered.width
+    } else {
+        centered.height
+    };
+    let window_px = ((window * len as f32).round() as usize).clamp(1, len);
+
+    // Sliding window sum; ties keep the position closest to the center
+    let mut sum: f64 = profile[..window_px].iter().sum();
+    let mut best = (sum, 0usize);
+    let center = (len - window_px) / 2;
+    for start in 1..=len - window_px {
+        sum += profile[start + window_px - 1] - profile[start - 1];
+        let closer = start.abs_diff(center) < best.1.abs_diff(center);
+        if sum > best.0 + 1e-9 || ((sum - best.0).abs() <= 1e-9 && closer) {
+            best = (sum, start);
+        }
+    }
+
+    let offset = best.1 as f32 / len as f32;
+    if horizontal {
+        CropRect {
+            x: offset.min(1.0 - centered.width),
+            ..centered
+        }
+    } else {
+        CropRect {
+            y: offset.min(1.0 - centered.height),
+            ..centered
+        }
+    }
+}
+
+/// Choose the crop a fit mode applies to `img` for a cell of aspect ratio `aspect`
+///
+/// Returns `None` for modes that show the whole image. Smart crop uses the focus point
+/// when one is known instead of estimating saliency.
+pub fn crop_for(
+    mode: FitMode,
+    img: &DynamicImage,
+    aspect: f32,
+    focus: Option<(f32, f32)>,
+) -> Option<CropRect> {
+    let (width, height) = img.dimensions();
+    match mode {
+        FitMode::Stretch | FitMode::Contain => None,
+        FitMode::Cover => Some(cover_crop(width, height, aspect, focus)),
+        FitMode::Smart if focus.is_some() => Some(cover_crop(width, height, aspect, focus)),
+        FitMode::Smart => Some(smart_crop(img, aspect)),
+    }
+}
+
+/// Pad `img` with `pad` so it has aspect ratio `aspect`, keeping it centered
+///
+/// Transparent images stay transparent; only the padding is opaque.
+pub fn letterbox(img: &DynamicImage, aspect: f32, pad: [u8; 3]) -> DynamicImage {
+    let (width, height) = img.dimensions();
+    let src_aspect = width as f32 / height as f32;
+    let (canvas_w, canvas_h) = if src_aspect > aspect {
+        (width, ((width as f32 / aspect).round() as u32).max(height))
+    } else {
+        (((height as f32 * aspect).round() as u32).max(width), height)
+    };
+
+    let (x, y) = (
+        ((canvas_w - width) / 2) as i64,
+        ((canvas_h - height) / 2) as i64,
+    );
+    if img.color().has_alpha() {
+        let [r, g, b] = pad;
+        let mut canvas = RgbaImage::from_pixel(canvas_w, canvas_h, Rgba([r, g, b, 255]));
+        image::imageops::replace(&mut canvas, &img.to_rgba8(), x, y);
+        DynamicImage::ImageRgba8(canvas)
+    } else {
+        let mut canvas = RgbImage::from_pixel(canvas_w, canvas_h, Rgb(pad));
+        image::imageops::replace(&mut canvas, &img.to_rgb8(), x, y);
+        DynamicImage::ImageRgb8(canvas)
+    }
+}
+
+/// What shows through the transparent parts of a tile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileBackground {
+    Color([u8; 3]),
+    /// The target image region the tile covers
+    Target,
+}
+
+impl Default for TileBackground {
+    fn default() -> Self {
+        Self::Color([0, 0, 0])
+    }
+}
+
+impl FromStr for TileBackground {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.eq_ignore_ascii_case("target") {
+            return Ok(Self::Target);
+        }
+        crate::manifest::parse_hex_rgb(s).map(Self::Color)
+    }
+}
+
+impl fmt::Display for TileBackground {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Color([r, g, b]) => write!(f, "#{r:02x}{g:02x}{b:02x}"),
+            Self::Target => f.write_str("target"),
+        }
+    }
+}
+
+/// Alpha-blend `tile` over an opaque `background` of the same size
+pub fn composite_over(tile: &RgbaImage, background: &RgbImage) -> RgbImage {
+    RgbImage::from_fn(tile.width(), tile.height(), |x, y| {
+        let Rgba([r, g, b, a]) = *tile.get_pixel(x, y);
+        let under = background.get_pixel(x, y);
+        let alpha = a as f32 / 255.0;
+        let blend = |top: u8, bottom: u8| {
+            (top as f32 * alpha + bottom as f32 * (1.0 - alpha)).round() as u8
+        };
+        Rgb([blend(r, under[0]), blend(g, under[1]), blend(b, under[2])])
+    })
+}
+
+/// The part of a material that is shown in a cell, before scaling to the cell size
+///
+/// Colors are computed on this view so they describe what actually ends up in the mosaic.
+pub fn visible_region(
+    img: &DynamicImage,
+    mode: FitMode,
+    crop: Option<CropRect>,
+    aspect: f32,
+    pad: [u8; 3],
+) -> DynamicImage {
+    match (mode, crop) {
+        (FitMode::Contain, _) => letterbox(img, aspect, pad),
+        (_, Some(crop)) => {
+            let (x, y, w, h) = crop.to_pixels(img.width(), img.height());
+            img.crop_imm(x, y, w, h)
+        }
+        (_, None) => img.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::ImageBuffer;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn test_parse_fit_mode() {
+        assert_eq!("cover".parse::<FitMode>().unwrap(), FitMode::Cover);
+        assert_eq!("Letterbox".parse::<FitMode>().unwrap(), FitMode::Contain);
+        assert!("zoom".parse::<FitMode>().is_err());
+        assert_eq!(FitMode::Smart.to_string(), "smart");
+    }
+
+    #[test]
+    fn test_cover_crop_centered() {
+        // 2:1 image into a square cell keeps the middle half
+        let crop = cover_crop(200, 100, 1.0, None);
+        assert_close(crop.x, 0.25);
+        assert_close(crop.width, 0.5);
+        assert_eq!(crop.to_pixels(200, 100), (50, 0, 100, 100));
+
+        // Tall image into a wide cell crops vertically
+        let crop = cover_crop(100, 200, 2.0, None);
+        assert_close(crop.y, 0.375);
+        assert_close(crop.height, 0.25);
+    }
+
+    #[test]
+    fn test_cover_crop_follows_focus_within_bounds() {
+        let crop = cover_crop(200, 100, 1.0, Some((0.9, 0.5)));
+        assert_close(crop.x, 0.5);
+
+        let crop = cover_crop(200, 100, 1.0, Some((0.4, 0.5)));
+        assert_close(crop.x, 0.15);
+    }
+
+    #[test]
+    fn test_smart_crop_finds_detail() {
+        // Flat wide image with a checkerboard on the right
+        let img = DynamicImage::ImageRgb8(ImageBuffer::from_fn(120, 40, |x, y| {
+            if x >= 80 && (x / 4 + y / 4) % 2 == 0 {
+                Rgb([255, 255, 255])
+            } else {
+                Rgb([0, 0, 0])
+            }
+        }));
+
+        let crop = smart_crop(&img, 1.0);
+
+        assert_close(crop.width, 1.0 / 3.0);
+        assert!(crop.x > 0.6, "crop at {}", crop.x);
+    }
+
+    #[test]
+    fn test_smart_crop_flat_image_stays_centered() {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(90, 30, Rgb([10, 20, 30])));
+
+        let crop = smart_crop(&img, 1.0);
+
+        assert_close(crop.x, 1.0 / 3.0);
+    }
+
+    #[test]
+    fn test_letterbox_pads_to_aspect() {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(100, 50, Rgb([255, 0, 0])));
+
+        let boxed = letterbox(&img, 1.0, [0, 0, 255]).to_rgb8();
+
+        assert_eq!(boxed.dimensions(), (100, 100));
+        assert_eq!(boxed.get_pixel(50, 10), &Rgb([0, 0, 255]));
+        assert_eq!(boxed.get_pixel(50, 50), &Rgb([255, 0, 0]));
+    }
+
+    #[test]
+    fn test_letterbox_keeps_transparency() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(100, 50, Rgba([255, 0, 0, 0])));
+
+        let boxed = letterbox(&img, 1.0, [0, 0, 255]).to_rgba8();
+
+        assert_eq!(boxed.get_pixel(50, 10), &Rgba([0, 0, 255, 255]));
+        assert_eq!(boxed.get_pixel(50, 50)[3], 0);
+    }
+
+    #[test]
+    fn test_composite_over_background() {
+        let tile = RgbaImage::from_fn(2, 1, |x, _| {
+            if x == 0 {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([255, 0, 0, 0])
+            }
+        });
+        let background = RgbImage::from_pixel(2, 1, Rgb([0, 0, 200]));
+
+        let composed = composite_over(&tile, &background);
+
+        assert_eq!(composed.get_pixel(0, 0), &Rgb([255, 0, 0]));
+        assert_eq!(composed.get_pixel(1, 0), &Rgb([0, 0, 200]));
+        assert_eq!(
+            "target".parse::<TileBackground>().unwrap(),
+            TileBackground::Target
+        );
+        assert_eq!(
+            "#0000c8".parse::<TileBackground>().unwrap(),
+            TileBackground::Color([0, 0, 200])
+        );
+    }
+
+    #[test]
+    fn test_visible_region_uses_crop() {
+        // Left half red, right half green; cover-cropping the right square shows only green
+        let img = DynamicImage::ImageRgb8(ImageBuffer::from_fn(200, 100, |x, _| {
+            if x < 100 {
+                Rgb([255, 0, 0])
+            } else {
+                Rgb([0, 255, 0])
+            }
+        }));
+        let crop = crop_for(FitMode::Cover, &img, 1.0, Some((1.0, 0.5)));
+
+        let region = visible_region(&img, FitMode::Cover, crop, 1.0, [0, 0, 0]).to_rgb8();
+
+        assert_eq!(region.dimensions(), (100, 100));
+        assert!(region.pixels().all(|p| *p == Rgb([0, 255, 0])));
+        assert!(crop_for(FitMode::Stretch, &img, 1.0, None).is_none());
+    }
+}
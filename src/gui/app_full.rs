@@ -3,6 +3,7 @@ use iced::widget::{
     button, checkbox, column, container, pick_list, progress_bar, row, scrollable, text, text_input,
 };
 use iced::{Application, Command, Element, Font, Length, Theme};
+use std::collections::HashSet;
 use std::path::PathBuf;
 use std::time::Instant;
 use tokio::sync::mpsc;
@@ -19,6 +20,8 @@ use mosaic_rust::optimizer::{MosaicOptimizer, OptimizationConfig};
 #[cfg(test)]
 use mosaic_rust::similarity::SimilarityDatabase;
 #[cfg(test)]
+use mosaic_rust::tile_grid::{TileGrid, TileId};
+#[cfg(test)]
 use mosaic_rust::{
     MosaicGenerator as MosaicGeneratorTrait, MosaicGeneratorImpl, Tile, UsageTracker,
 };
@@ -32,11 +35,14 @@ use image::{DynamicImage, ImageBuffer, Rgb};
 use kiddo::SquaredEuclidean;
 #[cfg(not(test))]
 use mosaic_rust::adjacency::{AdjacencyPenaltyCalculator, GridPosition};
+use mosaic_rust::importance::BASE_CANDIDATES;
 #[cfg(not(test))]
 use mosaic_rust::optimizer::{MosaicOptimizer, OptimizationConfig};
 #[cfg(not(test))]
 use mosaic_rust::similarity::SimilarityDatabase;
 #[cfg(not(test))]
+use mosaic_rust::tile_grid::{TileGrid, TileId};
+#[cfg(not(test))]
 use mosaic_rust::{
     MosaicGenerator as MosaicGeneratorTrait, MosaicGeneratorImpl, Tile, UsageTracker,
 };
@@ -1302,12 +1308,16 @@ type BigBucketKdTree = kiddo::float::kdtree::KdTree<f32, u64, 3, 256, u32>;
 struct InternalMosaicGenerator {
     tiles: Vec<Arc<Tile>>,
     kdtree: BigBucketKdTree,
+    /// Similarity database id of each tile, indexed like `tiles`
+    tile_ids: Vec<TileId>,
     usage_tracker: UsageTracker,
-    placed_tiles: Vec<Vec<Option<PathBuf>>>,
+    placed_tiles: TileGrid,
     grid_width: usize,
     grid_height: usize,
     similarity_db: SimilarityDatabase,
     adjacency_penalty_weight: f32,
+    /// Cells where every tile had reached its usage cap, so one was used past it
+    usage_overflow_count: usize,
 }
 
 impl InternalMosaicGenerator {
@@ -1316,11 +1326,24 @@ impl InternalMosaicGenerator {
         grid_width: usize,
         grid_height: usize,
         max_usage_per_image: usize,
-        similarity_db: SimilarityDatabase,
+        mut similarity_db: SimilarityDatabase,
         adjacency_penalty_weight: f32,
     ) -> Self {
         let mut kdtree = BigBucketKdTree::new();
 
+        // Grids refer to tiles by their similarity database id
+        let tile_ids = tiles
+            .iter()
+            .map(|tile| {
+                similarity_db.tile_id(&tile.path).unwrap_or_else(|| {
+                    similarity_db.add_tile(tile.path.clone(), tile.lab_color);
+                    similarity_db
+                        .tile_id(&tile.path)
+                        .expect("tile was just added")
+                })
+            })
+            .collect();
+
         // Build k-d tree for fast nearest neighbor search
         for (i, tile) in tiles.iter().enumerate() {
             kdtree.add(
@@ -1330,17 +1353,19 @@ impl InternalMosaicGenerator {
         }
 
         let usage_tracker = UsageTracker::new(max_usage_per_image);
-        let placed_tiles = vec![vec![None; grid_width]; grid_height];
+        let placed_tiles = TileGrid::new(grid_width, grid_height);
 
         Self {
             tiles,
             kdtree,
+            tile_ids,
             usage_tracker,
             placed_tiles,
             grid_width,
             grid_height,
             similarity_db,
             adjacency_penalty_weight,
+            usage_overflow_count: 0,
         }
     }
 
@@ -1360,7 +1385,7 @@ impl InternalMosaicGenerator {
             return Some(tile);
         }
 
-        // Stage 2: Fallback selection with relaxed usage constraints
+        // Stage 2: Fallback selection past the usage cap
         eprintln!(
             "⚠️ PRIMARY SELECTION FAILED for position ({}, {}), trying fallback...",
             position.x, position.y
@@ -1401,10 +1426,11 @@ impl InternalMosaicGenerator {
         let adjacency_calc =
             AdjacencyPenaltyCalculator::new(&self.similarity_db, self.adjacency_penalty_weight);
 
-        // Find multiple candidates - increased from 20 to 100 to match CLI
-        let candidates = self
-            .kdtree
-            .nearest_n::<SquaredEuclidean>(&[target_lab.l, target_lab.a, target_lab.b], 100);
+        // When used-up or adjacent tiles leave no candidate, double the search's reach
+        // until one fits or the whole tree has been covered
+        let tree_size = self.kdtree.size() as usize;
+        let mut candidate_count = tree_size.min(BASE_CANDIDATES);
+        let mut seen = HashSet::new();
 
         let mut best_tile = None;
         let mut best_score = f32::INFINITY;
@@ -1412,48 +1438,58 @@ impl InternalMosaicGenerator {
         let mut rejected_adjacency = 0;
         let mut candidates_evaluated = 0;
 
-        for candidate in candidates {
-            let tile_idx = candidate.item as usize;
-            if tile_idx >= self.tiles.len() {
-                continue; // Safety check
-            }
-            let tile = &self.tiles[tile_idx];
-            candidates_evaluated += 1;
+        loop {
+            let candidates = self.kdtree.nearest_n::<SquaredEuclidean>(
+                &[target_lab.l, target_lab.a, target_lab.b],
+                candidate_count,
+            );
 
-            // Check if we can still use this tile
-            if !self.usage_tracker.can_use_image(&tile.path) {
-                rejected_usage += 1;
-                continue;
-            }
+            for candidate in candidates {
+                let tile_idx = candidate.item as usize;
+                if tile_idx >= self.tiles.len() || !seen.insert(tile_idx) {
+                    continue; // Safety check, or already evaluated in a narrower search
+                }
+                let tile = &self.tiles[tile_idx];
+                candidates_evaluated += 1;
 
-            // Check basic adjacency constraint (no same image adjacent)
-            if !self.can_place_at_position(&tile.path, position) {
-                rejected_adjacency += 1;
-                continue;
-            }
+                // Check if we can still use this tile
+                if !self.usage_tracker.can_use_image(&tile.path) {
+                    rejected_usage += 1;
+                    continue;
+                }
 
-            // Calculate color distance
-            let color_distance = ((target_lab.l - tile.lab_color.l).powi(2)
-                + (target_lab.a - tile.lab_color.a).powi(2)
-                + (target_lab.b - tile.lab_color.b).powi(2))
-            .sqrt();
-
-            // Calculate adjacency penalty
-            let adjacency_penalty = adjacency_calc.calculate_penalty(
-                &tile.path,
-                position,
-                &self.placed_tiles,
-                self.grid_width,
-                self.grid_height,
-            );
+                // Check basic adjacency constraint (no same image adjacent)
+                if !self.can_place_at_position(self.tile_ids[tile_idx], position) {
+                    rejected_adjacency += 1;
+                    continue;
+                }
+
+                // Calculate color distance
+                let color_distance = ((target_lab.l - tile.lab_color.l).powi(2)
+                    + (target_lab.a - tile.lab_color.a).powi(2)
+                    + (target_lab.b - tile.lab_color.b).powi(2))
+                .sqrt();
+
+                // Calculate adjacency penalty
+                let adjacency_penalty = adjacency_calc.calculate_penalty(
+                    self.tile_ids[tile_idx],
+                    position,
+                    &self.placed_tiles,
+                );
 
-            // Combined score
-            let score = color_distance + adjacency_penalty;
+                // Combined score
+                let score = color_distance + adjacency_penalty;
 
-            if score < best_score {
-                best_score = score;
-                best_tile = Some(tile.clone());
+                if score < best_score {
+                    best_score = score;
+                    best_tile = Some(tile_idx);
+                }
+            }
+
+            if best_tile.is_some() || candidate_count >= tree_size {
+                break;
             }
+            candidate_count = (candidate_count * 2).min(tree_size);
         }
 
         if best_tile.is_none() {
@@ -1461,12 +1497,7 @@ impl InternalMosaicGenerator {
                 position.x, position.y, candidates_evaluated, rejected_usage, rejected_adjacency);
         }
 
-        if let Some(tile) = &best_tile {
-            self.usage_tracker.use_image(&tile.path);
-            self.placed_tiles[position.y][position.x] = Some(tile.path.clone());
-        }
-
-        best_tile
+        best_tile.map(|tile_idx| self.place_tile(tile_idx, position))
     }
 
     fn fallback_tile_selection(
@@ -1480,39 +1511,57 @@ impl InternalMosaicGenerator {
             return None;
         }
 
-        // Reset usage tracker and try again with only adjacency constraint
+        // No tile within its usage cap fits this cell. Go over the cap of the tile that is
+        // least over it, nearest color first, so extra uses spread over the pool instead of
+        // piling onto one material
+        self.usage_overflow_count += 1;
         eprintln!(
-            "🔄 FALLBACK: Resetting usage tracker for position ({}, {})",
+            "🔄 FALLBACK: No tile within its usage cap fits position ({}, {}), using one past its cap",
             position.x, position.y
         );
-        self.usage_tracker.reset();
 
-        let candidates = self
-            .kdtree
-            .nearest_n::<SquaredEuclidean>(&[target_lab.l, target_lab.a, target_lab.b], 100);
+        let candidates = self.kdtree.nearest_n::<SquaredEuclidean>(
+            &[target_lab.l, target_lab.a, target_lab.b],
+            self.tiles.len().min(BASE_CANDIDATES),
+        );
 
         let mut candidates_evaluated = 0;
         let mut rejected_adjacency = 0;
+        let mut least_used = None;
+        let mut least_over_cap = usize::MAX;
 
         for candidate in candidates {
             let tile_idx = candidate.item as usize;
             if tile_idx >= self.tiles.len() {
                 continue; // Safety check
             }
-            let tile = &self.tiles[tile_idx];
             candidates_evaluated += 1;
 
-            if self.can_place_at_position(&tile.path, position) {
-                eprintln!(
-                    "🔄 FALLBACK SUCCESS: Found tile {} after resetting usage tracker",
-                    tile.path.file_name().unwrap_or_default().to_string_lossy()
-                );
-                self.usage_tracker.use_image(&tile.path);
-                self.placed_tiles[position.y][position.x] = Some(tile.path.clone());
-                return Some(tile.clone());
-            } else {
+            if !self.can_place_at_position(self.tile_ids[tile_idx], position) {
                 rejected_adjacency += 1;
+                continue;
             }
+            let path = &self.tiles[tile_idx].path;
+            let over_cap = self
+                .usage_tracker
+                .get_usage_count(path)
+                .saturating_sub(self.usage_tracker.max_usage_for(path));
+            if over_cap < least_over_cap {
+                least_over_cap = over_cap;
+                least_used = Some(tile_idx);
+            }
+        }
+
+        if let Some(tile_idx) = least_used {
+            eprintln!(
+                "🔄 FALLBACK SUCCESS: Using tile {} past its usage cap",
+                self.tiles[tile_idx]
+                    .path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+            );
+            return Some(self.place_tile(tile_idx, position));
         }
 
         eprintln!("🔍 FALLBACK DEBUG for position ({}, {}): evaluated {} candidates, rejected {} for adjacency", 
@@ -1557,12 +1606,19 @@ impl InternalMosaicGenerator {
             color_distance,
             position.x, position.y);
 
+        Some(self.place_tile(tile_idx, position))
+    }
+
+    /// Record the tile at `tile_idx` as used and put it into the grid
+    fn place_tile(&mut self, tile_idx: usize, position: GridPosition) -> Arc<Tile> {
+        let tile = self.tiles[tile_idx].clone();
         self.usage_tracker.use_image(&tile.path);
-        self.placed_tiles[position.y][position.x] = Some(tile.path.clone());
-        Some(tile.clone())
+        self.placed_tiles
+            .set(position.x, position.y, Some(self.tile_ids[tile_idx]));
+        tile
     }
 
-    fn can_place_at_position(&self, tile_path: &PathBuf, position: GridPosition) -> bool {
+    fn can_place_at_position(&self, tile: TileId, position: GridPosition) -> bool {
         let x = position.x;
         let y = position.y;
 
@@ -1575,12 +1631,11 @@ impl InternalMosaicGenerator {
         ];
 
         for (nx, ny) in neighbors {
-            if nx < self.grid_width && ny < self.grid_height {
-                if let Some(placed_path) = &self.placed_tiles[ny][nx] {
-                    if placed_path == tile_path {
-                        return false;
-                    }
-                }
+            if nx < self.grid_width
+                && ny < self.grid_height
+                && self.placed_tiles.get(nx, ny) == Some(tile)
+            {
+                return false;
             }
         }
 
@@ -1698,11 +1753,7 @@ fn generate_mosaic_internal(
                         lab_color.b
                     );
                 }
-                Some(Arc::new(Tile {
-                    path: path.clone(),
-                    lab_color,
-                    aspect_ratio,
-                }))
+                Some(Arc::new(Tile::new(path.clone(), lab_color, aspect_ratio)))
             }
             Err(e) => {
                 if verbose {
@@ -1814,7 +1865,7 @@ fn generate_mosaic_internal(
             }
 
             // Show progress for every 1% of cells or when verbose
-            if cell_index % (total_cells / 100).max(1) == 0 || verbose {
+            if cell_index.is_multiple_of((total_cells / 100).max(1)) || verbose {
                 let cell_progress = cell_index as f32 / total_cells as f32;
                 let overall_progress = 0.5 + (cell_progress * 0.4); // 50% to 90%
                 let percentage = cell_progress * 100.0;
@@ -2096,6 +2147,13 @@ fn generate_mosaic_internal(
     }
 
     send_progress(0.9, "🎨 Grid processing completed".to_string());
+    if generator.usage_overflow_count > 0 {
+        log_message(&format!(
+            "⚠️ No material within its usage cap fit {} cells; {} materials were used past their cap",
+            generator.usage_overflow_count,
+            generator.usage_tracker.over_cap_count()
+        ));
+    }
 
     // Optimization phase
     if settings.enable_optimization && settings.adjacency_penalty_weight > 0.0 {
@@ -2137,7 +2195,11 @@ fn generate_mosaic_internal(
 
         for row in 0..settings.grid_h {
             for col in 0..settings.grid_w {
-                if let Some(tile_path) = &generator.placed_tiles[row as usize][col as usize] {
+                let tile_path = generator
+                    .placed_tiles
+                    .get(col as usize, row as usize)
+                    .and_then(|tile| generator.similarity_db.tile_path(tile));
+                if let Some(tile_path) = tile_path {
                     if let Ok(tile_img) = image::open(tile_path) {
                         let tile_rgb = tile_img.to_rgb8();
 
@@ -2251,11 +2313,13 @@ mod tests {
     use std::sync::{Arc, Mutex};
 
     // Custom writer to capture println! output for testing
+    #[allow(dead_code)]
     #[derive(Clone)]
     struct TestWriter {
         buffer: Arc<Mutex<Vec<String>>>,
     }
 
+    #[allow(dead_code)]
     impl TestWriter {
         fn new() -> Self {
             Self {
@@ -2281,14 +2345,16 @@ mod tests {
     #[test]
     fn test_mosaic_settings_default_verbose_logging_false() {
         let settings = MosaicSettings::default();
-        assert_eq!(settings.verbose_logging, false);
+        assert!(!settings.verbose_logging);
     }
 
     #[test]
     fn test_mosaic_settings_with_verbose_logging_enabled() {
-        let mut settings = MosaicSettings::default();
-        settings.verbose_logging = true;
-        assert_eq!(settings.verbose_logging, true);
+        let settings = MosaicSettings {
+            verbose_logging: true,
+            ..Default::default()
+        };
+        assert!(settings.verbose_logging);
     }
 
     #[test]
@@ -2306,11 +2372,11 @@ mod tests {
         let mut app = MosaicApp::new(()).0;
 
         // Test enabling verbose logging
-        app.update(Message::VerboseLoggingToggled(true));
+        let _ = app.update(Message::VerboseLoggingToggled(true));
         assert!(app.settings.verbose_logging);
 
         // Test disabling verbose logging
-        app.update(Message::VerboseLoggingToggled(false));
+        let _ = app.update(Message::VerboseLoggingToggled(false));
         assert!(!app.settings.verbose_logging);
     }
 
@@ -2335,11 +2401,11 @@ mod tests {
         assert_eq!(settings.grid_w, 10);
         assert_eq!(settings.grid_h, 10);
         assert_eq!(settings.total_tiles, Some(100));
-        assert_eq!(settings.auto_calculate, false);
+        assert!(!settings.auto_calculate);
         assert_eq!(settings.max_materials, 100);
         assert_eq!(settings.color_adjustment, 0.5);
-        assert_eq!(settings.enable_optimization, true);
-        assert_eq!(settings.verbose_logging, true);
+        assert!(settings.enable_optimization);
+        assert!(settings.verbose_logging);
         assert_eq!(settings.max_usage_per_image, 5);
         assert_eq!(settings.adjacency_penalty_weight, 0.2);
         assert_eq!(settings.optimization_iterations, 500);
@@ -2352,7 +2418,7 @@ mod tests {
         let mut captured_output = Vec::new();
 
         let mut log_message = |message: &str| {
-            captured_output.push(format!("{}", message));
+            captured_output.push(message.to_string());
         };
 
         log_message("Test message");
@@ -2476,8 +2542,8 @@ mod tests {
     #[test]
     fn test_mosaic_app_initial_verbose_state() {
         let (app, _) = MosaicApp::new(());
-        assert_eq!(
-            app.settings.verbose_logging, false,
+        assert!(
+            !app.settings.verbose_logging,
             "Verbose logging should be disabled by default"
         );
     }
@@ -2523,12 +2589,12 @@ mod tests {
         let mut app = MosaicApp::new(()).0;
 
         // Test valid input
-        app.update(Message::MaxUsagePerImageChanged("5".to_string()));
+        let _ = app.update(Message::MaxUsagePerImageChanged("5".to_string()));
         assert_eq!(app.settings.max_usage_per_image, 5);
         assert_eq!(app.max_usage_per_image_input, "5");
 
         // Test auto-calculation when set to 0
-        app.update(Message::MaxUsagePerImageChanged("0".to_string()));
+        let _ = app.update(Message::MaxUsagePerImageChanged("0".to_string()));
         // Should enable auto-calculation and compute based on default values: 1400/500 = 3
         let expected_auto_calculated = (1400.0_f32 / 500.0).ceil() as usize;
         assert_eq!(app.settings.max_usage_per_image, expected_auto_calculated);
@@ -2536,7 +2602,7 @@ mod tests {
 
         // Test invalid input (should not change the value)
         let prev_value = app.settings.max_usage_per_image;
-        app.update(Message::MaxUsagePerImageChanged("invalid".to_string()));
+        let _ = app.update(Message::MaxUsagePerImageChanged("invalid".to_string()));
         assert_eq!(app.settings.max_usage_per_image, prev_value);
     }
 
@@ -2545,20 +2611,20 @@ mod tests {
         let mut app = MosaicApp::new(()).0;
 
         // Test valid input
-        app.update(Message::AdjacencyPenaltyWeightChanged("0.5".to_string()));
+        let _ = app.update(Message::AdjacencyPenaltyWeightChanged("0.5".to_string()));
         assert_eq!(app.settings.adjacency_penalty_weight, 0.5);
         assert_eq!(app.adjacency_penalty_weight_input, "0.5");
 
         // Test clamping to 0.0-1.0 range
-        app.update(Message::AdjacencyPenaltyWeightChanged("1.5".to_string()));
+        let _ = app.update(Message::AdjacencyPenaltyWeightChanged("1.5".to_string()));
         assert_eq!(app.settings.adjacency_penalty_weight, 1.0);
 
-        app.update(Message::AdjacencyPenaltyWeightChanged("-0.5".to_string()));
+        let _ = app.update(Message::AdjacencyPenaltyWeightChanged("-0.5".to_string()));
         assert_eq!(app.settings.adjacency_penalty_weight, 0.0);
 
         // Test invalid input
         let prev_value = app.settings.adjacency_penalty_weight;
-        app.update(Message::AdjacencyPenaltyWeightChanged(
+        let _ = app.update(Message::AdjacencyPenaltyWeightChanged(
             "invalid".to_string(),
         ));
         assert_eq!(app.settings.adjacency_penalty_weight, prev_value);
@@ -2569,17 +2635,17 @@ mod tests {
         let mut app = MosaicApp::new(()).0;
 
         // Test valid input
-        app.update(Message::OptimizationIterationsChanged("2000".to_string()));
+        let _ = app.update(Message::OptimizationIterationsChanged("2000".to_string()));
         assert_eq!(app.settings.optimization_iterations, 2000);
         assert_eq!(app.optimization_iterations_input, "2000");
 
         // Test minimum constraint (should be at least 1)
-        app.update(Message::OptimizationIterationsChanged("0".to_string()));
+        let _ = app.update(Message::OptimizationIterationsChanged("0".to_string()));
         assert_eq!(app.settings.optimization_iterations, 1);
 
         // Test invalid input
         let prev_value = app.settings.optimization_iterations;
-        app.update(Message::OptimizationIterationsChanged(
+        let _ = app.update(Message::OptimizationIterationsChanged(
             "invalid".to_string(),
         ));
         assert_eq!(app.settings.optimization_iterations, prev_value);
@@ -2600,12 +2666,12 @@ mod tests {
 
         // In a real GUI test, we would check that the checkbox reflects the state
         // Here we just verify the initial state
-        assert_eq!(app.settings.verbose_logging, false);
+        assert!(!app.settings.verbose_logging);
 
         // Test state after toggling
         let mut app_toggled = app;
-        app_toggled.update(Message::VerboseLoggingToggled(true));
-        assert_eq!(app_toggled.settings.verbose_logging, true);
+        let _ = app_toggled.update(Message::VerboseLoggingToggled(true));
+        assert!(app_toggled.settings.verbose_logging);
     }
 
     // New fallback scenario tests
@@ -2615,16 +2681,16 @@ mod tests {
 
         // Create test tiles
         let tiles = vec![
-            Arc::new(Tile {
-                path: PathBuf::from("test1.png"),
-                lab_color: Lab::new(50.0, 0.0, 0.0),
-                aspect_ratio: 1.0,
-            }),
-            Arc::new(Tile {
-                path: PathBuf::from("test2.png"),
-                lab_color: Lab::new(75.0, 10.0, 5.0),
-                aspect_ratio: 1.0,
-            }),
+            Arc::new(Tile::new(
+                PathBuf::from("test1.png"),
+                Lab::new(50.0, 0.0, 0.0),
+                1.0,
+            )),
+            Arc::new(Tile::new(
+                PathBuf::from("test2.png"),
+                Lab::new(75.0, 10.0, 5.0),
+                1.0,
+            )),
         ];
 
         let similarity_db = SimilarityDatabase::new();
@@ -2656,26 +2722,26 @@ mod tests {
     fn test_can_place_at_position() {
         use std::path::PathBuf;
 
-        let tiles = vec![Arc::new(Tile {
-            path: PathBuf::from("test1.png"),
-            lab_color: Lab::new(50.0, 0.0, 0.0),
-            aspect_ratio: 1.0,
-        })];
+        let tiles = vec![Arc::new(Tile::new(
+            PathBuf::from("test1.png"),
+            Lab::new(50.0, 0.0, 0.0),
+            1.0,
+        ))];
 
         let similarity_db = SimilarityDatabase::new();
         let mut generator = InternalMosaicGenerator::new(tiles, 3, 3, 3, similarity_db, 0.3);
 
-        let tile_path = PathBuf::from("test1.png");
+        let tile = generator.tile_ids[0];
         let position = GridPosition { x: 1, y: 1 };
 
         // Should be able to place initially
-        assert!(generator.can_place_at_position(&tile_path, position));
+        assert!(generator.can_place_at_position(tile, position));
 
         // Place the tile at position (0, 1) - left of target position
-        generator.placed_tiles[1][0] = Some(tile_path.clone());
+        generator.placed_tiles.set(0, 1, Some(tile));
 
         // Should not be able to place same tile adjacent to itself
-        assert!(!generator.can_place_at_position(&tile_path, position));
+        assert!(!generator.can_place_at_position(tile, position));
     }
 
     #[test]
@@ -2683,16 +2749,16 @@ mod tests {
         use std::path::PathBuf;
 
         let tiles = vec![
-            Arc::new(Tile {
-                path: PathBuf::from("test1.png"),
-                lab_color: Lab::new(50.0, 0.0, 0.0),
-                aspect_ratio: 1.0,
-            }),
-            Arc::new(Tile {
-                path: PathBuf::from("test2.png"),
-                lab_color: Lab::new(75.0, 10.0, 5.0),
-                aspect_ratio: 1.0,
-            }),
+            Arc::new(Tile::new(
+                PathBuf::from("test1.png"),
+                Lab::new(50.0, 0.0, 0.0),
+                1.0,
+            )),
+            Arc::new(Tile::new(
+                PathBuf::from("test2.png"),
+                Lab::new(75.0, 10.0, 5.0),
+                1.0,
+            )),
         ];
 
         let similarity_db = SimilarityDatabase::new();
@@ -2725,11 +2791,11 @@ mod tests {
     fn test_final_fallback_selection() {
         use std::path::PathBuf;
 
-        let tiles = vec![Arc::new(Tile {
-            path: PathBuf::from("test1.png"),
-            lab_color: Lab::new(50.0, 0.0, 0.0),
-            aspect_ratio: 1.0,
-        })];
+        let tiles = vec![Arc::new(Tile::new(
+            PathBuf::from("test1.png"),
+            Lab::new(50.0, 0.0, 0.0),
+            1.0,
+        ))];
 
         let similarity_db = SimilarityDatabase::new();
         let mut generator = InternalMosaicGenerator::new(tiles, 2, 2, 3, similarity_db, 0.3);
@@ -2746,7 +2812,7 @@ mod tests {
 
         // Verify the tile was placed
         assert!(
-            generator.placed_tiles[0][0].is_some(),
+            generator.placed_tiles.get(0, 0).is_some(),
             "Tile should be placed in grid"
         );
     }
@@ -2774,7 +2840,7 @@ mod tests {
         let mut app = MosaicApp::new(()).0;
 
         // Test updating similarity database path
-        app.update(Message::SimilarityDbPathChanged(
+        let _ = app.update(Message::SimilarityDbPathChanged(
             "custom_db.json".to_string(),
         ));
         assert_eq!(app.similarity_db_path, "custom_db.json");
@@ -2805,16 +2871,16 @@ mod tests {
         use std::path::PathBuf;
 
         let tiles = vec![
-            Arc::new(Tile {
-                path: PathBuf::from("test1.png"),
-                lab_color: Lab::new(50.0, 0.0, 0.0),
-                aspect_ratio: 1.0,
-            }),
-            Arc::new(Tile {
-                path: PathBuf::from("test2.png"),
-                lab_color: Lab::new(75.0, 10.0, 5.0),
-                aspect_ratio: 1.0,
-            }),
+            Arc::new(Tile::new(
+                PathBuf::from("test1.png"),
+                Lab::new(50.0, 0.0, 0.0),
+                1.0,
+            )),
+            Arc::new(Tile::new(
+                PathBuf::from("test2.png"),
+                Lab::new(75.0, 10.0, 5.0),
+                1.0,
+            )),
         ];
 
         let similarity_db = SimilarityDatabase::new();
@@ -2845,7 +2911,7 @@ mod tests {
         for y in 0..2 {
             for x in 0..2 {
                 assert!(
-                    generator.placed_tiles[y][x].is_some(),
+                    generator.placed_tiles.get(x, y).is_some(),
                     "Position ({}, {}) should be filled",
                     x,
                     y
@@ -2859,16 +2925,16 @@ mod tests {
         use std::path::PathBuf;
 
         let tiles = vec![
-            Arc::new(Tile {
-                path: PathBuf::from("test1.png"),
-                lab_color: Lab::new(50.0, 0.0, 0.0),
-                aspect_ratio: 1.0,
-            }),
-            Arc::new(Tile {
-                path: PathBuf::from("test2.png"),
-                lab_color: Lab::new(75.0, 10.0, 5.0),
-                aspect_ratio: 1.0,
-            }),
+            Arc::new(Tile::new(
+                PathBuf::from("test1.png"),
+                Lab::new(50.0, 0.0, 0.0),
+                1.0,
+            )),
+            Arc::new(Tile::new(
+                PathBuf::from("test2.png"),
+                Lab::new(75.0, 10.0, 5.0),
+                1.0,
+            )),
         ];
 
         let similarity_db = SimilarityDatabase::new();
@@ -2886,17 +2952,19 @@ mod tests {
 
         // Verify the tile was placed
         assert!(
-            generator.placed_tiles[0][0].is_some(),
+            generator.placed_tiles.get(0, 0).is_some(),
             "Tile should be placed in grid"
         );
     }
 
     #[test]
     fn test_auto_calculate_max_usage_per_image_when_zero() {
-        let mut settings = MosaicSettings::default();
-        settings.max_usage_per_image = 0; // Set to 0 to trigger auto-calculation
-        settings.total_tiles = Some(1000);
-        settings.max_materials = 250;
+        let settings = MosaicSettings {
+            max_usage_per_image: 0, // Set to 0 to trigger auto-calculation
+            total_tiles: Some(1000),
+            max_materials: 250,
+            ..Default::default()
+        };
 
         let calculated_usage = auto_calculate_max_usage_per_image(&settings);
         assert_eq!(calculated_usage, 4); // 1000 / 250 = 4
@@ -2904,10 +2972,12 @@ mod tests {
 
     #[test]
     fn test_auto_calculate_max_usage_per_image_when_already_set() {
-        let mut settings = MosaicSettings::default();
-        settings.max_usage_per_image = 5; // Already set to non-zero
-        settings.total_tiles = Some(1000);
-        settings.max_materials = 250;
+        let settings = MosaicSettings {
+            max_usage_per_image: 5, // Already set to non-zero
+            total_tiles: Some(1000),
+            max_materials: 250,
+            ..Default::default()
+        };
 
         let calculated_usage = auto_calculate_max_usage_per_image(&settings);
         assert_eq!(calculated_usage, 5); // Should keep existing value
@@ -2915,10 +2985,12 @@ mod tests {
 
     #[test]
     fn test_auto_calculate_max_usage_per_image_handles_division_by_zero() {
-        let mut settings = MosaicSettings::default();
-        settings.max_usage_per_image = 0;
-        settings.total_tiles = Some(1000);
-        settings.max_materials = 0; // Division by zero case
+        let settings = MosaicSettings {
+            max_usage_per_image: 0,
+            total_tiles: Some(1000),
+            max_materials: 0, // Division by zero case
+            ..Default::default()
+        };
 
         let calculated_usage = auto_calculate_max_usage_per_image(&settings);
         assert_eq!(calculated_usage, 1); // Should default to 1 to avoid division by zero
@@ -2926,10 +2998,12 @@ mod tests {
 
     #[test]
     fn test_auto_calculate_max_usage_per_image_handles_no_total_tiles() {
-        let mut settings = MosaicSettings::default();
-        settings.max_usage_per_image = 0;
-        settings.total_tiles = None; // No total tiles set
-        settings.max_materials = 250;
+        let settings = MosaicSettings {
+            max_usage_per_image: 0,
+            total_tiles: None, // No total tiles set
+            max_materials: 250,
+            ..Default::default()
+        };
 
         let calculated_usage = auto_calculate_max_usage_per_image(&settings);
         assert_eq!(calculated_usage, 1); // Should default to 1 when no total tiles
@@ -2937,10 +3011,12 @@ mod tests {
 
     #[test]
     fn test_auto_calculate_max_usage_per_image_rounds_up() {
-        let mut settings = MosaicSettings::default();
-        settings.max_usage_per_image = 0;
-        settings.total_tiles = Some(1000);
-        settings.max_materials = 333; // 1000 / 333 = 3.003...
+        let settings = MosaicSettings {
+            max_usage_per_image: 0,
+            total_tiles: Some(1000),
+            max_materials: 333, // 1000 / 333 = 3.003...
+            ..Default::default()
+        };
 
         let calculated_usage = auto_calculate_max_usage_per_image(&settings);
         assert_eq!(calculated_usage, 4); // Should round up to ensure all tiles can be used
@@ -3129,14 +3205,15 @@ mod tests {
     }
 
     #[test]
-    fn test_usage_tracker_reset_in_fallback() {
+    fn test_fallback_keeps_usage_counts() {
         use std::path::PathBuf;
 
-        let tiles = vec![Arc::new(Tile {
-            path: PathBuf::from("test1.png"),
-            lab_color: Lab::new(50.0, 0.0, 0.0),
-            aspect_ratio: 1.0,
-        })];
+        let path = PathBuf::from("test1.png");
+        let tiles = vec![Arc::new(Tile::new(
+            path.clone(),
+            Lab::new(50.0, 0.0, 0.0),
+            1.0,
+        ))];
 
         let similarity_db = SimilarityDatabase::new();
         let mut generator = InternalMosaicGenerator::new(
@@ -3155,13 +3232,17 @@ mod tests {
         // First placement uses up the tile
         let result1 = generator.find_best_tile_for_position(&target_lab, position1);
         assert!(result1.is_some(), "First placement should succeed");
+        assert_eq!(generator.usage_overflow_count, 0);
 
-        // Second placement should succeed through fallback (usage tracker reset)
+        // Second placement goes past the cap instead of resetting the usage counts
         let result2 = generator.find_best_tile_for_position(&target_lab, position2);
         assert!(
             result2.is_some(),
             "Second placement should succeed with fallback"
         );
+        assert_eq!(generator.usage_tracker.get_usage_count(&path), 2);
+        assert_eq!(generator.usage_tracker.over_cap_count(), 1);
+        assert_eq!(generator.usage_overflow_count, 1);
     }
 
     #[test]
@@ -3194,7 +3275,7 @@ mod tests {
     fn test_auto_calculate_max_usage_message() {
         let message = Message::AutoCalculateMaxUsageToggled(true);
         match message {
-            Message::AutoCalculateMaxUsageToggled(val) => assert_eq!(val, true),
+            Message::AutoCalculateMaxUsageToggled(val) => assert!(val),
             _ => panic!("Expected AutoCalculateMaxUsageToggled message"),
         }
     }
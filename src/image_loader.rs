@@ -0,0 +1,400 @@
+use anyhow::Result;
+use image::{DynamicImage, GenericImageView, ImageBuffer, ImageFormat, ImageReader};
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read};
+use std::path::Path;
+
+use crate::archive;
+use crate::limits::{LimitError, ResourceLimits};
+
+/// Default maximum edge length of the proxy image used for color analysis
+pub const DEFAULT_ANALYSIS_SIZE: u32 = 128;
+
+/// A reduced-resolution proxy of a source image used for feature extraction
+#[derive(Debug, Clone)]
+pub struct AnalysisImage {
+    /// Downscaled image used to compute colors and signatures
+    pub image: DynamicImage,
+    /// Width of the source image before any scaling
+    pub source_width: u32,
+    /// Height of the source image before any scaling
+    pub source_height: u32,
+}
+
+impl AnalysisImage {
+    /// Aspect ratio of the source image (not the proxy, which may be rounded)
+    pub fn aspect_ratio(&self) -> f32 {
+        self.source_width as f32 / self.source_height as f32
+    }
+}
+
+/// An encoded image on disk or inside an archive
+///
+/// Archive entries are decompressed once when the source is opened, so reading the header
+/// and decoding the image afterwards don't inflate the entry twice. Plain files are read
+/// on demand.
+pub enum ImageSource<'a> {
+    File(&'a Path),
+    Entry { path: &'a Path, bytes: Vec<u8> },
+}
+
+impl<'a> ImageSource<'a> {
+    /// Open a source, reading the entry's bytes if the path points inside an archive
+    pub fn open(path: &'a Path, limits: &ResourceLimits) -> Result<Self> {
+        if archive::split_entry_path(path).is_some() {
+            let bytes = archive::read(path, limits)?;
+            return Ok(Self::Entry { path, bytes });
+        }
+        Ok(Self::File(path))
+    }
+
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::File(path) | Self::Entry { path, .. } => path,
+        }
+    }
+
+    /// Read the image's dimensions from its header
+    pub fn dimensions(&self, limits: &ResourceLimits) -> Result<(u32, u32)> {
+        let (width, height) = match self {
+            Self::File(path) => image::image_dimensions(path)?,
+            Self::Entry { path, bytes } => reader_for(path, bytes)?.into_dimensions()?,
+        };
+        limits.check_image_dimensions(self.path(), width, height)?;
+        Ok((width, height))
+    }
+
+    /// Decode the image at full resolution
+    pub fn decode(&self, limits: &ResourceLimits) -> Result<DynamicImage> {
+        match self {
+            Self::File(path) => {
+                let mut reader = ImageReader::open(path)?;
+                reader.limits(limits.image_limits());
+                reader
+                    .decode()
+                    .map_err(|err| limits.map_decode_error(path, err))
+            }
+            Self::Entry { path, bytes } => decode_bytes(path, bytes, limits),
+        }
+    }
+
+    /// Decode the image for analysis; see [`open_for_analysis`]
+    pub fn decode_for_analysis(
+        &self,
+        max_dimension: u32,
+        limits: &ResourceLimits,
+    ) -> Result<AnalysisImage> {
+        let path = self.path();
+        if max_dimension > 0 && ImageFormat::from_path(path).ok() == Some(ImageFormat::Jpeg) {
+            let scaled = match self {
+                Self::File(path) => {
+                    let reader = BufReader::new(File::open(path)?);
+                    try_jpeg_scaled(reader, path, max_dimension, limits)?
+                }
+                Self::Entry { path, bytes } => {
+                    try_jpeg_scaled(Cursor::new(bytes), path, max_dimension, limits)?
+                }
+            };
+            if let Some(analysis) = scaled {
+                return Ok(analysis);
+            }
+        }
+
+        let img = self.decode(limits)?;
+        let (source_width, source_height) = img.dimensions();
+
+        Ok(AnalysisImage {
+            image: downsample(img, max_dimension),
+            source_width,
+            source_height,
+        })
+    }
+}
+
+/// Decode an image for analysis, keeping its longest edge at or below `max_dimension`
+///
+/// JPEG files are decoded with DCT scaling (1/2, 1/4 or 1/8) so the full-resolution
+/// buffer is never allocated. Other formats are decoded normally and downsampled
+/// immediately. A `max_dimension` of 0 disables the proxy and returns the full image.
+/// Images beyond `limits` fail with a [`LimitError`] instead of being decoded.
+pub fn open_for_analysis(
+    path: &Path,
+    max_dimension: u32,
+    limits: &ResourceLimits,
+) -> Result<AnalysisImage> {
+    ImageSource::open(path, limits)?.decode_for_analysis(max_dimension, limits)
+}
+
+/// Run the scaled JPEG decode, surfacing limit violations but not other failures
+///
+/// Anything the scaled path can't handle falls back to the regular decoder (`Ok(None)`).
+fn try_jpeg_scaled<R: Read>(
+    reader: R,
+    path: &Path,
+    max_dimension: u32,
+    limits: &ResourceLimits,
+) -> Result<Option<AnalysisImage>> {
+    match decode_jpeg_scaled(reader, path, max_dimension, limits) {
+        Ok(analysis) => Ok(analysis),
+        Err(err) if err.is::<LimitError>() => Err(err),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Decode a JPEG at the smallest DCT scale that still covers `max_dimension`
+///
+/// Returns `Ok(None)` for pixel formats the fast path does not convert (CMYK, 16-bit).
+fn decode_jpeg_scaled<R: Read>(
+    reader: R,
+    path: &Path,
+    max_dimension: u32,
+    limits: &ResourceLimits,
+) -> Result<Option<AnalysisImage>> {
+    let mut decoder = jpeg_decoder::Decoder::new(reader);
+    decoder.set_max_decoding_buffer_size(limits.max_alloc_bytes.min(usize::MAX as u64) as usize);
+    decoder.read_info()?;
+
+    let info = match decoder.info() {
+        Some(info) => info,
+        None => return Ok(None),
+    };
+    let source_width = info.width as u32;
+    let source_height = info.height as u32;
+    limits.check_image_dimensions(path, source_width, source_height)?;
+
+    let requested = max_dimension.min(u16::MAX as u32) as u16;
+    let (width, height) = decoder.scale(requested, requested)?;
+    let pixels = decoder.decode()?;
+
+    let img = match info.pixel_format {
+        jpeg_decoder::PixelFormat::RGB24 => {
+            ImageBuffer::from_raw(width as u32, height as u32, pixels).map(DynamicImage::ImageRgb8)
+        }
+        jpeg_decoder::PixelFormat::L8 => {
+            ImageBuffer::from_raw(width as u32, height as u32, pixels).map(DynamicImage::ImageLuma8)
+        }
+        _ => None,
+    };
+
+    Ok(img.map(|img| AnalysisImage {
+        image: downsample(img, max_dimension),
+        source_width,
+        source_height,
+    }))
+}
+
+/// Decode an image at full resolution, from disk or from inside an archive
+pub fn open_image(path: &Path, limits: &ResourceLimits) -> Result<DynamicImage> {
+    ImageSource::open(path, limits)?.decode(limits)
+}
+
+/// Read an image's dimensions from its header, from disk or from inside an archive
+pub fn image_dimensions(path: &Path, limits: &ResourceLimits) -> Result<(u32, u32)> {
+    ImageSource::open(path, limits)?.dimensions(limits)
+}
+
+/// Decode in-memory image data, using the entry name to pick the format
+fn decode_bytes(path: &Path, bytes: &[u8], limits: &ResourceLimits) -> Result<DynamicImage> {
+    let mut reader = reader_for(path, bytes)?;
+    reader.limits(limits.image_limits());
+    reader
+        .decode()
+        .map_err(|err| limits.map_decode_error(path, err))
+}
+
+fn reader_for<'a>(path: &Path, bytes: &'a [u8]) -> Result<ImageReader<Cursor<&'a [u8]>>> {
+    let mut reader = ImageReader::new(Cursor::new(bytes));
+    match ImageFormat::from_path(path) {
+        Ok(format) => reader.set_format(format),
+        Err(_) => reader = reader.with_guessed_format()?,
+    }
+    Ok(reader)
+}
+
+/// Shrink an image so its longest edge is at most `max_dimension`, preserving aspect ratio
+fn downsample(img: DynamicImage, max_dimension: u32) -> DynamicImage {
+    let (width, height) = img.dimensions();
+    if max_dimension == 0 || (width <= max_dimension && height <= max_dimension) {
+        return img;
+    }
+    img.thumbnail(max_dimension, max_dimension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+    use tempfile::tempdir;
+
+    fn save_solid(path: &Path, width: u32, height: u32, color: Rgb<u8>) {
+        let img: RgbImage = ImageBuffer::from_fn(width, height, |_, _| color);
+        img.save(path).unwrap();
+    }
+
+    #[test]
+    fn test_open_for_analysis_jpeg_is_scaled() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("large.jpg");
+        save_solid(&path, 800, 400, Rgb([200, 40, 40]));
+
+        let analysis = open_for_analysis(&path, 64, &ResourceLimits::default()).unwrap();
+
+        assert_eq!(analysis.source_width, 800);
+        assert_eq!(analysis.source_height, 400);
+        assert_eq!(analysis.aspect_ratio(), 2.0);
+        let (w, h) = analysis.image.dimensions();
+        assert!(w <= 64 && h <= 64);
+
+        // Color should survive the reduced decode
+        let pixel = analysis.image.to_rgb8().get_pixel(w / 2, h / 2).0;
+        assert!((pixel[0] as i32 - 200).abs() < 10);
+        assert!((pixel[1] as i32 - 40).abs() < 10);
+    }
+
+    #[test]
+    fn test_open_for_analysis_png_is_downsampled() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("large.png");
+        save_solid(&path, 300, 600, Rgb([10, 200, 10]));
+
+        let analysis = open_for_analysis(&path, 50, &ResourceLimits::default()).unwrap();
+
+        assert_eq!(analysis.source_width, 300);
+        assert_eq!(analysis.source_height, 600);
+        let (w, h) = analysis.image.dimensions();
+        assert_eq!(h, 50);
+        assert_eq!(w, 25);
+    }
+
+    #[test]
+    fn test_open_for_analysis_small_image_untouched() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("small.png");
+        save_solid(&path, 20, 10, Rgb([0, 0, 255]));
+
+        let analysis = open_for_analysis(&path, 128, &ResourceLimits::default()).unwrap();
+
+        assert_eq!(analysis.image.dimensions(), (20, 10));
+    }
+
+    #[test]
+    fn test_open_for_analysis_zero_disables_proxy() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("full.jpg");
+        save_solid(&path, 256, 128, Rgb([128, 128, 128]));
+
+        let analysis = open_for_analysis(&path, 0, &ResourceLimits::default()).unwrap();
+
+        assert_eq!(analysis.image.dimensions(), (256, 128));
+    }
+
+    #[test]
+    fn test_open_from_zip_archive() {
+        let dir = tempdir().unwrap();
+        let jpg = dir.path().join("photo.jpg");
+        save_solid(&jpg, 400, 200, Rgb([30, 60, 200]));
+
+        let archive_path = dir.path().join("pack.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&archive_path).unwrap());
+        zip.start_file("photos/photo.jpg", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        std::io::Write::write_all(&mut zip, &std::fs::read(&jpg).unwrap()).unwrap();
+        zip.finish().unwrap();
+
+        let entry = archive_path.join("photos/photo.jpg");
+        assert_eq!(
+            image_dimensions(&entry, &ResourceLimits::default()).unwrap(),
+            (400, 200)
+        );
+        assert_eq!(
+            open_image(&entry, &ResourceLimits::default())
+                .unwrap()
+                .dimensions(),
+            (400, 200)
+        );
+
+        let analysis = open_for_analysis(&entry, 64, &ResourceLimits::default()).unwrap();
+        assert_eq!(analysis.aspect_ratio(), 2.0);
+        let (w, h) = analysis.image.dimensions();
+        assert!(w <= 64 && h <= 64);
+    }
+
+    #[test]
+    fn test_archive_source_is_read_once() {
+        let dir = tempdir().unwrap();
+        let png = dir.path().join("tile.png");
+        save_solid(&png, 90, 30, Rgb([200, 10, 10]));
+
+        let archive_path = dir.path().join("pack.tar");
+        let mut tar = tar::Builder::new(File::create(&archive_path).unwrap());
+        tar.append_path_with_name(&png, "tile.png").unwrap();
+        tar.finish().unwrap();
+
+        let entry = archive_path.join("tile.png");
+        let limits = ResourceLimits::default();
+        let source = ImageSource::open(&entry, &limits).unwrap();
+        assert!(matches!(source, ImageSource::Entry { .. }));
+
+        // Tar entries are read from the file on every access, so these only succeed if
+        // the source kept the bytes it read when it was opened
+        std::fs::remove_file(&archive_path).unwrap();
+        assert_eq!(source.dimensions(&limits).unwrap(), (90, 30));
+        let analysis = source.decode_for_analysis(64, &limits).unwrap();
+        assert_eq!(analysis.aspect_ratio(), 3.0);
+    }
+
+    #[test]
+    fn test_oversized_images_fail_with_limit_error() {
+        let dir = tempdir().unwrap();
+        let limits = ResourceLimits {
+            max_image_dimension: 100,
+            ..Default::default()
+        };
+
+        for name in ["wide.jpg", "wide.png"] {
+            let path = dir.path().join(name);
+            save_solid(&path, 200, 50, Rgb([0, 0, 0]));
+
+            for err in [
+                image_dimensions(&path, &limits).unwrap_err(),
+                open_image(&path, &limits).unwrap_err(),
+                open_for_analysis(&path, 64, &limits).unwrap_err(),
+            ] {
+                assert!(matches!(
+                    err.downcast_ref::<LimitError>(),
+                    Some(LimitError::ImageDimensions { width: 200, .. })
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_allocation_limit() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("big.png");
+        save_solid(&path, 100, 100, Rgb([0, 0, 0]));
+        let limits = ResourceLimits {
+            max_alloc_bytes: 1000,
+            ..Default::default()
+        };
+
+        let err = open_image(&path, &limits).unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<LimitError>(),
+            Some(LimitError::Allocation { .. })
+        ));
+    }
+
+    #[test]
+    fn test_open_for_analysis_invalid_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("broken.jpg");
+        std::fs::write(&path, "not an image").unwrap();
+
+        assert!(open_for_analysis(&path, 64, &ResourceLimits::default()).is_err());
+        assert!(
+            open_for_analysis(Path::new("missing.jpg"), 64, &ResourceLimits::default()).is_err()
+        );
+    }
+}
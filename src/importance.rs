@@ -0,0 +1,239 @@
+use anyhow::Result;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::texture::sobel;
+
+/// Candidates taken from the k-d tree for a cell of average importance
+pub const BASE_CANDIDATES: usize = 100;
+/// Default share of the automatic map that comes from distance to the center
+pub const DEFAULT_CENTER_BIAS: f32 = 0.3;
+/// Extra adjacency weight the optimizer gives a cell of full importance
+const OPTIMIZER_GAIN: f32 = 3.0;
+/// Pixels per cell side when measuring edges for the automatic map
+const EDGE_SAMPLES: u32 = 8;
+
+/// Where per-cell importance comes from
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ImportanceSource {
+    /// Every cell is equally important
+    #[default]
+    Uniform,
+    /// Edge density of the target, blended with a bias toward the center
+    Auto,
+    /// Grayscale image aligned with the target; white is most important
+    Image(PathBuf),
+}
+
+impl FromStr for ImportanceSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "none" | "uniform" => Ok(Self::Uniform),
+            "auto" => Ok(Self::Auto),
+            _ => Ok(Self::Image(PathBuf::from(s))),
+        }
+    }
+}
+
+impl fmt::Display for ImportanceSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Uniform => f.write_str("none"),
+            Self::Auto => f.write_str("auto"),
+            Self::Image(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// Importance of every grid cell, from 0.0 (background) to 1.0 (subject)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportanceMap {
+    values: Vec<Vec<f32>>,
+}
+
+impl ImportanceMap {
+    /// Average a grayscale map over each cell
+    ///
+    /// Like region masks, the map is scaled to the grid, so it only needs the target's
+    /// proportions, not its resolution.
+    pub fn from_image(img: &DynamicImage, grid_width: usize, grid_height: usize) -> Self {
+        let luma = img.to_luma8();
+        let (map_width, map_height) = luma.dimensions();
+        let mut values = vec![vec![0.0; grid_width]; grid_height];
+        if map_width == 0 || map_height == 0 {
+            return Self { values };
+        }
+
+        for (y, row) in values.iter_mut().enumerate() {
+            let y0 = y as u32 * map_height / grid_height as u32;
+            let y1 = ((y as u32 + 1) * map_height / grid_height as u32).max(y0 + 1);
+            for (x, value) in row.iter_mut().enumerate() {
+                let x0 = x as u32 * map_width / grid_width as u32;
+                let x1 = ((x as u32 + 1) * map_width / grid_width as u32).max(x0 + 1);
+
+                let (mut sum, mut count) = (0u64, 0u64);
+                for py in y0..y1.min(map_height) {
+                    for px in x0..x1.min(map_width) {
+                        sum += luma.get_pixel(px, py)[0] as u64;
+                        count += 1;
+                    }
+                }
+                *value = sum as f32 / (count.max(1) * 255) as f32;
+            }
+        }
+        Self { values }
+    }
+
+    /// Estimate importance from the target's edge density and each cell's distance to
+    /// the center
+    ///
+    /// `center_bias` is the share of the center term: 0.0 uses edges only, 1.0 ignores them.
+    pub fn automatic(
+        target: &DynamicImage,
+        grid_width: usize,
+        grid_height: usize,
+        center_bias: f32,
+    ) -> Self {
+        let (width, height) = (
+            grid_width as u32 * EDGE_SAMPLES,
+            grid_height as u32 * EDGE_SAMPLES,
+        );
+        let luma = target
+            .resize_exact(width, height, FilterType::Triangle)
+            .to_luma8();
+
+        let mut edges = vec![vec![0.0f32; grid_width]; grid_height];
+        for y in 1..height.saturating_sub(1) {
+            for x in 1..width.saturating_sub(1) {
+                let (gx, gy) = sobel(&luma, x, y);
+                edges[(y / EDGE_SAMPLES) as usize][(x / EDGE_SAMPLES) as usize] +=
+                    (gx * gx + gy * gy).sqrt();
+            }
+        }
+        let max_edges = edges.iter().flatten().copied().fold(0.0f32, f32::max);
+
+        let center_bias = center_bias.clamp(0.0, 1.0);
+        let values = edges
+            .iter()
+            .enumerate()
+            .map(|(y, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(|(x, &edge)| {
+                        let edge = if max_edges > 0.0 {
+                            edge / max_edges
+                        } else {
+                            0.0
+                        };
+                        let dx = (x as f32 + 0.5) / grid_width as f32 * 2.0 - 1.0;
+                        let dy = (y as f32 + 0.5) / grid_height as f32 * 2.0 - 1.0;
+                        let center = 1.0 - ((dx * dx + dy * dy) / 2.0).sqrt();
+                        (1.0 - center_bias) * edge + center_bias * center
+                    })
+                    .collect()
+            })
+            .collect();
+        Self { values }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> f32 {
+        self.values
+            .get(y)
+            .and_then(|row| row.get(x))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Move more important cells ahead, keeping the given order among equally important ones
+    pub fn prioritize(&self, cells: &mut [(usize, usize)]) {
+        cells.sort_by(|&(ax, ay), &(bx, by)| self.get(bx, by).total_cmp(&self.get(ax, ay)));
+    }
+
+    /// How many color candidates to consider for a cell: half the base pool for the least
+    /// important cells up to twice the base pool for the most important
+    pub fn candidate_count(&self, x: usize, y: usize) -> usize {
+        (BASE_CANDIDATES as f32 * (0.5 + 1.5 * self.get(x, y))).round() as usize
+    }
+
+    /// Per-cell weights for adjacency costs, from 1.0 up to `1.0 + OPTIMIZER_GAIN`
+    pub fn cell_weights(&self) -> Vec<Vec<f32>> {
+        self.values
+            .iter()
+            .map(|row| row.iter().map(|v| 1.0 + OPTIMIZER_GAIN * v).collect())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, Luma, Rgb, RgbImage};
+
+    fn most_important(map: &ImportanceMap, width: usize, height: usize) -> Vec<(usize, usize)> {
+        let mut cells: Vec<(usize, usize)> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .collect();
+        map.prioritize(&mut cells);
+        cells
+    }
+
+    #[test]
+    fn test_from_image_scales_to_grid() {
+        // 40x20 map, bright on the right half
+        let map = GrayImage::from_fn(40, 20, |x, _| if x >= 20 { Luma([255]) } else { Luma([0]) });
+        let importance = ImportanceMap::from_image(&DynamicImage::ImageLuma8(map), 4, 2);
+
+        assert_eq!(importance.get(0, 0), 0.0);
+        assert_eq!(importance.get(3, 1), 1.0);
+        assert_eq!(
+            most_important(&importance, 4, 2)[..4],
+            [(2, 0), (3, 0), (2, 1), (3, 1)]
+        );
+        assert_eq!(importance.candidate_count(0, 0), BASE_CANDIDATES / 2);
+        assert_eq!(importance.candidate_count(3, 0), BASE_CANDIDATES * 2);
+        assert_eq!(importance.cell_weights()[0][3], 1.0 + OPTIMIZER_GAIN);
+    }
+
+    #[test]
+    fn test_automatic_favors_edges_and_center() {
+        // A detailed checkerboard in the top-left cell of an otherwise flat image
+        let target = RgbImage::from_fn(60, 60, |x, y| {
+            if x < 20 && y < 20 && (x / 2 + y / 2) % 2 == 0 {
+                Rgb([255, 255, 255])
+            } else {
+                Rgb([0, 0, 0])
+            }
+        });
+        let target = DynamicImage::ImageRgb8(target);
+
+        let edges_only = ImportanceMap::automatic(&target, 3, 3, 0.0);
+        assert_eq!(most_important(&edges_only, 3, 3)[0], (0, 0));
+        assert_eq!(edges_only.get(2, 2), 0.0);
+
+        let center_only = ImportanceMap::automatic(&target, 3, 3, 1.0);
+        assert_eq!(most_important(&center_only, 3, 3)[0], (1, 1));
+        assert!(center_only.get(1, 1) > center_only.get(0, 1));
+        assert!(center_only.get(0, 1) > center_only.get(0, 0));
+    }
+
+    #[test]
+    fn test_parse_source() {
+        assert_eq!(
+            "auto".parse::<ImportanceSource>().unwrap(),
+            ImportanceSource::Auto
+        );
+        assert_eq!(
+            "none".parse::<ImportanceSource>().unwrap(),
+            ImportanceSource::Uniform
+        );
+        assert_eq!(
+            "faces.png".parse::<ImportanceSource>().unwrap(),
+            ImportanceSource::Image(PathBuf::from("faces.png"))
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use palette::Lab;
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// Outcome of ingesting a single material file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaterialStatus {
+    Accepted,
+    AspectMismatch,
+    DecodeError,
+    Filtered,
+    Duplicate,
+}
+
+impl MaterialStatus {
+    pub const ALL: [MaterialStatus; 5] = [
+        MaterialStatus::Accepted,
+        MaterialStatus::AspectMismatch,
+        MaterialStatus::DecodeError,
+        MaterialStatus::Filtered,
+        MaterialStatus::Duplicate,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MaterialStatus::Accepted => "accepted",
+            MaterialStatus::AspectMismatch => "aspect mismatch",
+            MaterialStatus::DecodeError => "decode error",
+            MaterialStatus::Filtered => "filtered",
+            MaterialStatus::Duplicate => "duplicate",
+        }
+    }
+}
+
+/// What happened to one material file, with whatever was learned about it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MaterialRecord {
+    pub path: PathBuf,
+    pub status: MaterialStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aspect_ratio: Option<f32>,
+    /// Computed (or manifest-supplied) average color as `[L, a, b]`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lab_color: Option<[f32; 3]>,
+    /// Share of the image covered by the dominant color, when that descriptor is used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_coverage: Option<f32>,
+}
+
+impl MaterialRecord {
+    pub fn new(path: PathBuf, status: MaterialStatus) -> Self {
+        Self {
+            path,
+            status,
+            reason: None,
+            width: None,
+            height: None,
+            aspect_ratio: None,
+            lab_color: None,
+            color_coverage: None,
+        }
+    }
+
+    pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    pub fn with_dimensions(mut self, width: u32, height: u32) -> Self {
+        self.width = Some(width);
+        self.height = Some(height);
+        self.aspect_ratio = Some(width as f32 / height as f32);
+        self
+    }
+
+    pub fn with_color(mut self, lab: Lab) -> Self {
+        self.lab_color = Some([lab.l, lab.a, lab.b]);
+        self
+    }
+
+    pub fn with_coverage(mut self, coverage: Option<f32>) -> Self {
+        self.color_coverage = coverage;
+        self
+    }
+}
+
+/// Per-file account of material ingestion, written as JSON and summarized on the CLI
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IngestionReport {
+    pub target_aspect: f32,
+    pub aspect_tolerance: f32,
+    /// Set when no material matched the target aspect and the filter was dropped
+    pub aspect_fallback: bool,
+    pub materials: Vec<MaterialRecord>,
+}
+
+impl IngestionReport {
+    pub fn new(target_aspect: f32, aspect_tolerance: f32) -> Self {
+        Self {
+            target_aspect,
+            aspect_tolerance,
+            ..Default::default()
+        }
+    }
+
+    pub fn count(&self, status: MaterialStatus) -> usize {
+        self.materials.iter().filter(|m| m.status == status).count()
+    }
+
+    pub fn record_for(&self, path: &Path) -> Option<&MaterialRecord> {
+        self.materials.iter().find(|m| m.path == path)
+    }
+
+    /// Render a per-status count table for the terminal
+    pub fn summary_table(&self) -> String {
+        let mut table = String::new();
+        let _ = writeln!(table, "  {:<16} {:>7}", "Status", "Files");
+        for status in MaterialStatus::ALL {
+            let _ = writeln!(table, "  {:<16} {:>7}", status.label(), self.count(status));
+        }
+        let _ = write!(table, "  {:<16} {:>7}", "total", self.materials.len());
+        if self.aspect_fallback {
+            let _ = write!(
+                table,
+                "\n  No material matched aspect {:.3} ± {}; accepted materials were loaded without the aspect filter",
+                self.target_aspect, self.aspect_tolerance
+            );
+        }
+        table
+    }
+
+    /// Save the report as pretty-printed JSON
+    pub fn save_to_file(&self, path: &Path) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn sample_report() -> IngestionReport {
+        let mut report = IngestionReport::new(1.5, 0.1);
+        report.materials = vec![
+            MaterialRecord::new(PathBuf::from("a.jpg"), MaterialStatus::Accepted)
+                .with_dimensions(300, 200)
+                .with_color(Lab::new(50.0, 1.0, -1.0)),
+            MaterialRecord::new(PathBuf::from("b.jpg"), MaterialStatus::AspectMismatch)
+                .with_dimensions(100, 100),
+            MaterialRecord::new(PathBuf::from("c.jpg"), MaterialStatus::DecodeError)
+                .with_reason("invalid JPEG"),
+            MaterialRecord::new(PathBuf::from("a.jpg"), MaterialStatus::Duplicate),
+        ];
+        report
+    }
+
+    #[test]
+    fn test_counts_and_summary_table() {
+        let report = sample_report();
+
+        assert_eq!(report.count(MaterialStatus::Accepted), 1);
+        assert_eq!(report.count(MaterialStatus::Filtered), 0);
+
+        let table = report.summary_table();
+        assert!(table.contains("aspect mismatch"));
+        assert!(table
+            .lines()
+            .any(|l| l.split_whitespace().collect::<Vec<_>>() == ["total", "4"]));
+        assert!(!table.contains("without the aspect filter"));
+    }
+
+    #[test]
+    fn test_summary_mentions_aspect_fallback() {
+        let mut report = sample_report();
+        report.aspect_fallback = true;
+
+        assert!(report.summary_table().contains("without the aspect filter"));
+    }
+
+    #[test]
+    fn test_save_to_file_json_shape() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("report.json");
+
+        sample_report().save_to_file(&path).unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let materials = json["materials"].as_array().unwrap();
+        assert_eq!(materials.len(), 4);
+        assert_eq!(materials[0]["status"], "accepted");
+        assert_eq!(materials[0]["width"], 300);
+        assert_eq!(materials[0]["lab_color"][0], 50.0);
+        assert_eq!(materials[1]["status"], "aspect_mismatch");
+        assert_eq!(materials[2]["reason"], "invalid JPEG");
+        // Unknown fields are omitted rather than written as null
+        assert!(materials[2].get("width").is_none());
+        assert_eq!(json["aspect_fallback"], false);
+    }
+}
@@ -1,14 +1,35 @@
 use image::DynamicImage;
 use palette::{FromColor, Lab, Srgb};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub mod adjacency;
+pub mod archive;
 pub mod color_adjustment;
+pub mod color_descriptor;
+pub mod coverage;
+pub mod dither;
+pub mod fit;
 pub mod grid_visualizer;
+pub mod image_loader;
+pub mod importance;
+pub mod ingestion_report;
+pub mod limits;
+pub mod linear_light;
+pub mod manifest;
 pub mod optimizer;
+pub mod placement;
+pub mod region_mask;
+pub mod reuse_distance;
+pub mod sampling;
 pub mod similarity;
+pub mod subset;
+pub mod sweep;
+pub mod texture;
+pub mod tile_grid;
 pub mod time_tracker;
+pub mod usage_penalty;
+pub mod variants;
 
 // GUI-related data structures for testing
 #[cfg(test)]
@@ -47,6 +68,42 @@ pub struct Tile {
     pub lab_color: Lab,
     #[allow(dead_code)]
     pub aspect_ratio: f32,
+    /// Free-form labels supplied by a material manifest
+    pub tags: Vec<String>,
+    /// Matching preference; scores are divided by this, so values above 1.0 favor the tile
+    pub weight: f32,
+    /// Per-file usage cap overriding the global `max_usage_per_image`
+    pub max_usage: Option<usize>,
+    /// Point of interest in normalized coordinates (0.0-1.0), if known
+    pub focus_point: Option<(f32, f32)>,
+    /// Part of the image shown in a cell, chosen by the fit mode (`None` shows all of it)
+    pub crop: Option<fit::CropRect>,
+    /// Orientation of this candidate; variants of one file share its path
+    pub transform: variants::TileTransform,
+    /// Texture of the visible region, measured only when texture matching is enabled
+    pub texture: Option<texture::TextureDescriptor>,
+}
+
+impl Tile {
+    pub fn new(path: PathBuf, lab_color: Lab, aspect_ratio: f32) -> Self {
+        Self {
+            path,
+            lab_color,
+            aspect_ratio,
+            tags: Vec::new(),
+            weight: 1.0,
+            max_usage: None,
+            focus_point: None,
+            crop: None,
+            transform: variants::TileTransform::Identity,
+            texture: None,
+        }
+    }
+
+    /// Check whether the tile carries the given tag
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
 }
 
 pub trait MosaicGenerator {
@@ -58,6 +115,7 @@ pub trait MosaicGenerator {
 pub struct UsageTracker {
     usage_counts: HashMap<PathBuf, usize>,
     max_usage_per_image: usize,
+    usage_limits: HashMap<PathBuf, usize>,
 }
 
 impl UsageTracker {
@@ -65,12 +123,26 @@ impl UsageTracker {
         Self {
             usage_counts: HashMap::new(),
             max_usage_per_image,
+            usage_limits: HashMap::new(),
         }
     }
 
+    /// Override the global usage cap for a single image
+    pub fn set_max_usage(&mut self, path: &Path, max_usage: usize) {
+        self.usage_limits.insert(path.to_path_buf(), max_usage);
+    }
+
+    /// Get the usage cap that applies to an image
+    pub fn max_usage_for(&self, path: &Path) -> usize {
+        *self
+            .usage_limits
+            .get(path)
+            .unwrap_or(&self.max_usage_per_image)
+    }
+
     pub fn can_use_image(&self, path: &PathBuf) -> bool {
         let current_usage = self.usage_counts.get(path).unwrap_or(&0);
-        *current_usage < self.max_usage_per_image
+        *current_usage < self.max_usage_for(path)
     }
 
     pub fn use_image(&mut self, path: &PathBuf) {
@@ -83,6 +155,14 @@ impl UsageTracker {
         *self.usage_counts.get(path).unwrap_or(&0)
     }
 
+    /// Number of images used more often than their cap allows
+    pub fn over_cap_count(&self) -> usize {
+        self.usage_counts
+            .iter()
+            .filter(|(path, count)| **count > self.max_usage_for(path))
+            .count()
+    }
+
     pub fn reset(&mut self) {
         self.usage_counts.clear();
     }
@@ -90,8 +170,32 @@ impl UsageTracker {
 
 pub struct MosaicGeneratorImpl;
 
+/// Alpha-weighted average Lab color, or `None` when the image is fully transparent
+fn average_lab_by_alpha(img: &DynamicImage) -> Option<Lab> {
+    let (mut sum_l, mut sum_a, mut sum_b, mut total) = (0.0, 0.0, 0.0, 0.0);
+    for pixel in img.to_rgba8().pixels() {
+        let weight = pixel[3] as f32 / 255.0;
+        if weight == 0.0 {
+            continue;
+        }
+        let lab: Lab = Lab::from_color(Srgb::new(pixel[0], pixel[1], pixel[2]).into_format());
+        sum_l += lab.l * weight;
+        sum_a += lab.a * weight;
+        sum_b += lab.b * weight;
+        total += weight;
+    }
+    (total > 0.0).then(|| Lab::new(sum_l / total, sum_a / total, sum_b / total))
+}
+
 impl MosaicGenerator for MosaicGeneratorImpl {
     fn calculate_average_lab(img: &DynamicImage) -> Lab {
+        // Transparent pixels don't show, so average by opacity
+        if img.color().has_alpha() {
+            if let Some(lab) = average_lab_by_alpha(img) {
+                return lab;
+            }
+        }
+
         let rgb_img = img.to_rgb8();
         let (width, height) = rgb_img.dimensions();
         let total_pixels = (width * height) as f32;
@@ -143,6 +247,26 @@ mod tests {
         assert!((lab.b - 67.20).abs() < 1.0);
     }
 
+    #[test]
+    fn test_calculate_average_lab_ignores_transparent_pixels() {
+        let img = image::RgbaImage::from_fn(10, 10, |x, _| {
+            if x < 5 {
+                image::Rgba([255, 255, 255, 255])
+            } else {
+                image::Rgba([0, 0, 0, 0])
+            }
+        });
+
+        let lab = MosaicGeneratorImpl::calculate_average_lab(&DynamicImage::ImageRgba8(img));
+
+        assert!((lab.l - 100.0).abs() < 0.5);
+
+        // Fully transparent images fall back to the stored colors
+        let clear = image::RgbaImage::from_pixel(2, 2, image::Rgba([0, 0, 0, 0]));
+        let lab = MosaicGeneratorImpl::calculate_average_lab(&DynamicImage::ImageRgba8(clear));
+        assert!(lab.l.abs() < 0.01);
+    }
+
     #[test]
     fn test_calculate_average_lab_grayscale() {
         // Test with a grayscale image (middle gray)
@@ -228,11 +352,11 @@ mod tests {
 
     #[test]
     fn test_tile_creation() {
-        let tile = Tile {
-            path: PathBuf::from("test.png"),
-            lab_color: Lab::new(50.0, 0.0, 0.0),
-            aspect_ratio: 16.0 / 9.0,
-        };
+        let tile = Tile::new(
+            PathBuf::from("test.png"),
+            Lab::new(50.0, 0.0, 0.0),
+            16.0 / 9.0,
+        );
 
         assert_eq!(tile.path.to_str().unwrap(), "test.png");
         assert_eq!(tile.aspect_ratio, 16.0 / 9.0);
@@ -283,6 +407,39 @@ mod tests {
         assert!(!tracker.can_use_image(&path2));
     }
 
+    #[test]
+    fn test_usage_tracker_per_image_limit() {
+        let mut tracker = UsageTracker::new(3);
+        let capped = PathBuf::from("capped.png");
+        let normal = PathBuf::from("normal.png");
+
+        tracker.set_max_usage(&capped, 1);
+        assert_eq!(tracker.max_usage_for(&capped), 1);
+        assert_eq!(tracker.max_usage_for(&normal), 3);
+
+        tracker.use_image(&capped);
+        tracker.use_image(&normal);
+        assert!(!tracker.can_use_image(&capped));
+        assert!(tracker.can_use_image(&normal));
+    }
+
+    #[test]
+    fn test_usage_tracker_over_cap_count() {
+        let mut tracker = UsageTracker::new(2);
+        let capped = PathBuf::from("capped.png");
+        let normal = PathBuf::from("normal.png");
+        tracker.set_max_usage(&capped, 1);
+
+        tracker.use_image(&capped);
+        tracker.use_image(&normal);
+        tracker.use_image(&normal);
+        assert_eq!(tracker.over_cap_count(), 0);
+
+        tracker.use_image(&capped);
+        tracker.use_image(&normal);
+        assert_eq!(tracker.over_cap_count(), 2);
+    }
+
     #[test]
     fn test_usage_tracker_reset() {
         let mut tracker = UsageTracker::new(1);
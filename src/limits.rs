@@ -0,0 +1,333 @@
+use image::error::{ImageError, LimitErrorKind};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Default maximum width or height of any decoded image
+pub const DEFAULT_MAX_IMAGE_DIMENSION: u32 = 20_000;
+/// Default maximum size of a single decode or output buffer (same as `image`'s default)
+pub const DEFAULT_MAX_ALLOC_BYTES: u64 = 512 * 1024 * 1024;
+/// Default maximum number of grid cells
+pub const DEFAULT_MAX_GRID_CELLS: u64 = 1_000_000;
+/// Default maximum number of pixels in the output mosaic
+pub const DEFAULT_MAX_OUTPUT_PIXELS: u64 = 100_000_000;
+
+/// Limits that keep untrusted targets and materials from exhausting memory
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResourceLimits {
+    /// Maximum width and height of a decoded image
+    pub max_image_dimension: u32,
+    /// Maximum bytes for a single image buffer (decoded, archived or output)
+    pub max_alloc_bytes: u64,
+    /// Maximum `grid_w * grid_h`
+    pub max_grid_cells: u64,
+    /// Maximum width * height of the output mosaic
+    pub max_output_pixels: u64,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            max_image_dimension: DEFAULT_MAX_IMAGE_DIMENSION,
+            max_alloc_bytes: DEFAULT_MAX_ALLOC_BYTES,
+            max_grid_cells: DEFAULT_MAX_GRID_CELLS,
+            max_output_pixels: DEFAULT_MAX_OUTPUT_PIXELS,
+        }
+    }
+}
+
+/// A resource limit that an input or setting exceeded
+#[derive(Debug, Clone, PartialEq)]
+pub enum LimitError {
+    ImageDimensions {
+        path: PathBuf,
+        width: u32,
+        height: u32,
+        max: u32,
+    },
+    Allocation {
+        path: PathBuf,
+        /// Requested size, when known before decoding
+        requested: Option<u64>,
+        max: u64,
+    },
+    GridCells {
+        grid_w: u32,
+        grid_h: u32,
+        max: u64,
+    },
+    OutputPixels {
+        width: u32,
+        height: u32,
+        max: u64,
+    },
+    /// Cost matrices for placing every material at least once
+    Assignment {
+        materials: usize,
+        cells: usize,
+        requested: u64,
+        max: u64,
+    },
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ImageDimensions {
+                path,
+                width,
+                height,
+                max,
+            } => write!(
+                f,
+                "Image {path:?} is {width}x{height}, exceeding the {max}px dimension limit"
+            ),
+            Self::Allocation {
+                path,
+                requested: Some(requested),
+                max,
+            } => write!(
+                f,
+                "Image {path:?} needs {requested} bytes, exceeding the {max} byte allocation limit"
+            ),
+            Self::Allocation {
+                path,
+                requested: None,
+                max,
+            } => write!(
+                f,
+                "Decoding {path:?} exceeds the {max} byte allocation limit"
+            ),
+            Self::GridCells {
+                grid_w,
+                grid_h,
+                max,
+            } => write!(
+                f,
+                "Grid {grid_w}x{grid_h} has {} cells, exceeding the limit of {max}",
+                *grid_w as u64 * *grid_h as u64
+            ),
+            Self::OutputPixels { width, height, max } => write!(
+                f,
+                "Output {width}x{height} has {} pixels, exceeding the limit of {max}",
+                *width as u64 * *height as u64
+            ),
+            Self::Assignment {
+                materials,
+                cells,
+                requested,
+                max,
+            } => write!(
+                f,
+                "Assigning {materials} materials to {cells} cells needs {requested} bytes, exceeding the {max} byte allocation limit"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LimitError {}
+
+impl ResourceLimits {
+    /// Limits to hand to `image`'s decoders
+    pub fn image_limits(&self) -> image::Limits {
+        let mut limits = image::Limits::default();
+        limits.max_image_width = Some(self.max_image_dimension);
+        limits.max_image_height = Some(self.max_image_dimension);
+        limits.max_alloc = Some(self.max_alloc_bytes);
+        limits
+    }
+
+    /// Reject images whose header declares dimensions beyond the limit
+    pub fn check_image_dimensions(
+        &self,
+        path: &Path,
+        width: u32,
+        height: u32,
+    ) -> Result<(), LimitError> {
+        if width > self.max_image_dimension || height > self.max_image_dimension {
+            return Err(LimitError::ImageDimensions {
+                path: path.to_path_buf(),
+                width,
+                height,
+                max: self.max_image_dimension,
+            });
+        }
+        Ok(())
+    }
+
+    /// Reject raw buffers (e.g. archive entries) larger than the allocation limit
+    pub fn check_allocation(&self, path: &Path, bytes: u64) -> Result<(), LimitError> {
+        if bytes > self.max_alloc_bytes {
+            return Err(LimitError::Allocation {
+                path: path.to_path_buf(),
+                requested: Some(bytes),
+                max: self.max_alloc_bytes,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn check_grid(&self, grid_w: u32, grid_h: u32) -> Result<(), LimitError> {
+        if grid_w as u64 * grid_h as u64 > self.max_grid_cells {
+            return Err(LimitError::GridCells {
+                grid_w,
+                grid_h,
+                max: self.max_grid_cells,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check the materials x cells cost matrices of the use-every-material assignment
+    ///
+    /// Each pair takes an `f32` distance and an `f64` regret.
+    pub fn check_assignment(&self, materials: usize, cells: usize) -> Result<(), LimitError> {
+        let requested = (materials as u64)
+            .saturating_mul(cells as u64)
+            .saturating_mul(12);
+        if requested > self.max_alloc_bytes {
+            return Err(LimitError::Assignment {
+                materials,
+                cells,
+                requested,
+                max: self.max_alloc_bytes,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check an output image of `width` x `height` RGB8 pixels before allocating it
+    pub fn check_output(&self, path: &Path, width: u32, height: u32) -> Result<(), LimitError> {
+        let pixels = width as u64 * height as u64;
+        if pixels > self.max_output_pixels {
+            return Err(LimitError::OutputPixels {
+                width,
+                height,
+                max: self.max_output_pixels,
+            });
+        }
+        self.check_allocation(path, pixels * 3)
+    }
+
+    /// Convert `image`'s limit errors into a [`LimitError`], passing other errors through
+    pub fn map_decode_error(&self, path: &Path, err: ImageError) -> anyhow::Error {
+        match err {
+            ImageError::Limits(ref limit) => match limit.kind() {
+                LimitErrorKind::DimensionError => {
+                    // The decoder doesn't report the size; read it from the header if possible
+                    let (width, height) = image::image_dimensions(path).unwrap_or((0, 0));
+                    LimitError::ImageDimensions {
+                        path: path.to_path_buf(),
+                        width,
+                        height,
+                        max: self.max_image_dimension,
+                    }
+                    .into()
+                }
+                LimitErrorKind::InsufficientMemory => LimitError::Allocation {
+                    path: path.to_path_buf(),
+                    requested: None,
+                    max: self.max_alloc_bytes,
+                }
+                .into(),
+                _ => err.into(),
+            },
+            err => err.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_image_dimensions() {
+        let limits = ResourceLimits {
+            max_image_dimension: 100,
+            ..Default::default()
+        };
+
+        assert!(limits
+            .check_image_dimensions(Path::new("a.png"), 100, 100)
+            .is_ok());
+        assert!(matches!(
+            limits.check_image_dimensions(Path::new("a.png"), 101, 10),
+            Err(LimitError::ImageDimensions { width: 101, .. })
+        ));
+    }
+
+    #[test]
+    fn test_check_grid() {
+        let limits = ResourceLimits {
+            max_grid_cells: 100,
+            ..Default::default()
+        };
+
+        assert!(limits.check_grid(10, 10).is_ok());
+        assert_eq!(
+            limits.check_grid(11, 10),
+            Err(LimitError::GridCells {
+                grid_w: 11,
+                grid_h: 10,
+                max: 100
+            })
+        );
+        // No overflow for huge values
+        assert!(limits.check_grid(u32::MAX, u32::MAX).is_err());
+    }
+
+    #[test]
+    fn test_check_output_pixels_and_allocation() {
+        let limits = ResourceLimits {
+            max_output_pixels: 1000,
+            max_alloc_bytes: 2000,
+            ..Default::default()
+        };
+        let path = Path::new("out.png");
+
+        assert!(limits.check_output(path, 20, 30).is_ok());
+        assert!(matches!(
+            limits.check_output(path, 40, 30),
+            Err(LimitError::OutputPixels { .. })
+        ));
+        // Within the pixel limit but too large to allocate as RGB8
+        let limits = ResourceLimits {
+            max_alloc_bytes: 100,
+            ..limits
+        };
+        assert!(matches!(
+            limits.check_output(path, 20, 30),
+            Err(LimitError::Allocation {
+                requested: Some(1800),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_check_assignment() {
+        let limits = ResourceLimits {
+            max_alloc_bytes: 1200,
+            ..Default::default()
+        };
+
+        assert!(limits.check_assignment(10, 10).is_ok());
+        assert_eq!(
+            limits.check_assignment(10, 11),
+            Err(LimitError::Assignment {
+                materials: 10,
+                cells: 11,
+                requested: 1320,
+                max: 1200
+            })
+        );
+    }
+
+    #[test]
+    fn test_image_limits() {
+        let limits = ResourceLimits::default().image_limits();
+        assert_eq!(limits.max_image_width, Some(DEFAULT_MAX_IMAGE_DIMENSION));
+        assert_eq!(limits.max_image_height, Some(DEFAULT_MAX_IMAGE_DIMENSION));
+        assert_eq!(limits.max_alloc, Some(DEFAULT_MAX_ALLOC_BYTES));
+    }
+}
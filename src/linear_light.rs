@@ -0,0 +1,99 @@
+use image::{DynamicImage, Rgb};
+use std::sync::OnceLock;
+
+/// Rec. 709 luminance coefficients, valid for linear RGB only
+pub const LUMINANCE_WEIGHTS: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+static TO_LINEAR: OnceLock<[f32; 256]> = OnceLock::new();
+
+/// Decode an sRGB byte to linear light (0.0-1.0)
+pub fn to_linear(value: u8) -> f32 {
+    TO_LINEAR.get_or_init(|| {
+        std::array::from_fn(|i| {
+            let v = i as f32 / 255.0;
+            if v <= 0.04045 {
+                v / 12.92
+            } else {
+                ((v + 0.055) / 1.055).powf(2.4)
+            }
+        })
+    })[value as usize]
+}
+
+/// Encode a linear-light value (0.0-1.0) as an sRGB byte
+pub fn to_srgb(value: f32) -> u8 {
+    let v = value.clamp(0.0, 1.0);
+    let encoded = if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round() as u8
+}
+
+/// Relative luminance of a linear RGB color
+pub fn luminance(linear: [f32; 3]) -> f32 {
+    linear
+        .iter()
+        .zip(LUMINANCE_WEIGHTS)
+        .map(|(c, w)| c * w)
+        .sum()
+}
+
+/// Average color computed in linear light and re-encoded as sRGB
+///
+/// Pixels are weighted by alpha, like the gamma-encoded average.
+pub fn average_rgb(img: &DynamicImage) -> Rgb<u8> {
+    let (mut sum, mut total) = ([0.0f64; 3], 0.0f64);
+    for pixel in img.to_rgba8().pixels() {
+        let weight = pixel[3] as f64 / 255.0;
+        for c in 0..3 {
+            sum[c] += to_linear(pixel[c]) as f64 * weight;
+        }
+        total += weight;
+    }
+    if total == 0.0 {
+        return Rgb([0, 0, 0]);
+    }
+    Rgb(sum.map(|s| to_srgb((s / total) as f32)))
+}
+
+/// Mean relative luminance of an image, in linear light
+pub fn mean_luminance(img: &DynamicImage) -> f32 {
+    let rgb = img.to_rgb8();
+    let total: f64 = rgb
+        .pixels()
+        .map(|p| luminance([to_linear(p[0]), to_linear(p[1]), to_linear(p[2])]) as f64)
+        .sum();
+    (total / (rgb.width() as f64 * rgb.height() as f64).max(1.0)) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    #[test]
+    fn test_round_trip() {
+        for value in 0..=255u8 {
+            assert_eq!(to_srgb(to_linear(value)), value);
+        }
+        assert_eq!(to_linear(0), 0.0);
+        assert!((to_linear(255) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_average_of_black_and_white_is_light_grey() {
+        // Half the light of white is sRGB 188, not the gamma-encoded midpoint 128
+        let img = DynamicImage::ImageRgb8(RgbImage::from_fn(2, 1, |x, _| {
+            if x == 0 {
+                Rgb([0, 0, 0])
+            } else {
+                Rgb([255, 255, 255])
+            }
+        }));
+
+        assert_eq!(average_rgb(&img), Rgb([188, 188, 188]));
+        assert!((mean_luminance(&img) - 0.5).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,321 @@
+use anyhow::{anyhow, bail, Context, Result};
+use palette::{FromColor, Lab, Srgb};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use crate::Tile;
+
+/// A material image together with the optional metadata an asset pipeline may know about it
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialEntry {
+    pub path: PathBuf,
+    pub tags: Vec<String>,
+    pub weight: Option<f32>,
+    pub max_usage: Option<usize>,
+    /// Point of interest in normalized coordinates (0.0-1.0)
+    pub focus_point: Option<(f32, f32)>,
+    /// Precomputed average color; skips decoding the image during ingestion
+    pub lab_color: Option<Lab>,
+}
+
+impl MaterialEntry {
+    /// Create an entry without any metadata (used for plain directory scans)
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            tags: Vec::new(),
+            weight: None,
+            max_usage: None,
+            focus_point: None,
+            lab_color: None,
+        }
+    }
+
+    /// Copy the entry's metadata onto a loaded tile
+    pub fn apply_to(&self, tile: &mut Tile) {
+        tile.tags = self.tags.clone();
+        if let Some(weight) = self.weight {
+            tile.weight = weight;
+        }
+        tile.max_usage = self.max_usage;
+        tile.focus_point = self.focus_point;
+    }
+}
+
+/// A list of materials loaded from a CSV or JSON manifest
+#[derive(Debug, Clone, Default)]
+pub struct MaterialManifest {
+    pub entries: Vec<MaterialEntry>,
+}
+
+/// JSON representation of a manifest entry
+#[derive(Debug, Deserialize)]
+struct JsonEntry {
+    path: PathBuf,
+    #[serde(default)]
+    tags: Vec<String>,
+    weight: Option<f32>,
+    max_usage: Option<usize>,
+    focus: Option<[f32; 2]>,
+    color: Option<String>,
+}
+
+/// CSV representation of a manifest entry (tags are separated by `;`)
+#[derive(Debug, Deserialize)]
+struct CsvEntry {
+    path: PathBuf,
+    tags: Option<String>,
+    weight: Option<f32>,
+    max_usage: Option<usize>,
+    focus_x: Option<f32>,
+    focus_y: Option<f32>,
+    color: Option<String>,
+}
+
+impl MaterialManifest {
+    /// Load a manifest, choosing the parser from the file extension (`.csv` or `.json`)
+    ///
+    /// Relative material paths are resolved against the manifest's directory.
+    pub fn load(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase());
+
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read material manifest {path:?}"))?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+        let mut manifest = match extension.as_deref() {
+            Some("json") => Self::from_json(&contents)?,
+            Some("csv") => Self::from_csv(&contents)?,
+            _ => bail!("Unsupported manifest format {path:?} (expected .csv or .json)"),
+        };
+
+        for entry in &mut manifest.entries {
+            if entry.path.is_relative() {
+                entry.path = base_dir.join(&entry.path);
+            }
+        }
+
+        Ok(manifest)
+    }
+
+    /// Parse a JSON manifest: an array of `{ "path", "tags", "weight", "max_usage", "focus", "color" }`
+    pub fn from_json(contents: &str) -> Result<Self> {
+        let raw: Vec<JsonEntry> = serde_json::from_str(contents)?;
+        let entries = raw
+            .into_iter()
+            .map(|e| {
+                build_entry(
+                    e.path,
+                    e.tags,
+                    e.weight,
+                    e.max_usage,
+                    e.focus.map(|[x, y]| (x, y)),
+                    e.color,
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { entries })
+    }
+
+    /// Parse a CSV manifest with a header row; only the `path` column is required
+    pub fn from_csv(contents: &str) -> Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(contents.as_bytes());
+
+        let mut entries = Vec::new();
+        for record in reader.deserialize::<CsvEntry>() {
+            let e = record?;
+            let tags = e
+                .tags
+                .map(|t| {
+                    t.split(';')
+                        .map(|tag| tag.trim().to_string())
+                        .filter(|tag| !tag.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default();
+            let focus = match (e.focus_x, e.focus_y) {
+                (Some(x), Some(y)) => Some((x, y)),
+                (None, None) => None,
+                _ => bail!("Material {:?} needs both focus_x and focus_y", e.path),
+            };
+            entries.push(build_entry(
+                e.path,
+                tags,
+                e.weight,
+                e.max_usage,
+                focus,
+                e.color,
+            )?);
+        }
+        Ok(Self { entries })
+    }
+}
+
+fn build_entry(
+    path: PathBuf,
+    tags: Vec<String>,
+    weight: Option<f32>,
+    max_usage: Option<usize>,
+    focus_point: Option<(f32, f32)>,
+    color: Option<String>,
+) -> Result<MaterialEntry> {
+    if let Some(w) = weight {
+        if !(w > 0.0 && w.is_finite()) {
+            bail!("Material {path:?} has invalid weight {w} (must be positive)");
+        }
+    }
+    if let Some((x, y)) = focus_point {
+        if !(0.0..=1.0).contains(&x) || !(0.0..=1.0).contains(&y) {
+            bail!("Material {path:?} has focus point ({x}, {y}) outside 0.0-1.0");
+        }
+    }
+    let lab_color = match color.as_deref().filter(|c| !c.is_empty()) {
+        Some(hex) => Some(parse_hex_color(hex).with_context(|| format!("Material {path:?}"))?),
+        None => None,
+    };
+
+    Ok(MaterialEntry {
+        path,
+        tags,
+        weight,
+        max_usage,
+        focus_point,
+        lab_color,
+    })
+}
+
+/// Parse an sRGB hex color (`#rrggbb` or `rrggbb`) into its 8-bit channels
+pub fn parse_hex_rgb(hex: &str) -> Result<[u8; 3]> {
+    let digits = hex.trim().trim_start_matches('#');
+    if digits.len() != 6 || !digits.is_ascii() {
+        bail!("Invalid color {hex:?} (expected #rrggbb)");
+    }
+    let channel = |i: usize| {
+        u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| anyhow!("Invalid color {hex:?}"))
+    };
+    Ok([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Parse an sRGB hex color (`#rrggbb` or `rrggbb`) into Lab
+pub fn parse_hex_color(hex: &str) -> Result<Lab> {
+    let [r, g, b] = parse_hex_rgb(hex)?;
+    let srgb = Srgb::new(r, g, b).into_format::<f32>();
+    Ok(Lab::from_color(srgb))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_from_json_full_entry() {
+        let json = r##"[
+            {"path": "a.jpg", "tags": ["sponsor", "logo"], "weight": 2.0,
+             "max_usage": 1, "focus": [0.25, 0.75], "color": "#ff0000"},
+            {"path": "b.jpg"}
+        ]"##;
+
+        let manifest = MaterialManifest::from_json(json).unwrap();
+
+        assert_eq!(manifest.entries.len(), 2);
+        let a = &manifest.entries[0];
+        assert_eq!(a.tags, vec!["sponsor", "logo"]);
+        assert_eq!(a.weight, Some(2.0));
+        assert_eq!(a.max_usage, Some(1));
+        assert_eq!(a.focus_point, Some((0.25, 0.75)));
+        let lab = a.lab_color.unwrap();
+        assert!((lab.l - 53.24).abs() < 1.0);
+        assert!((lab.a - 80.09).abs() < 1.0);
+
+        assert_eq!(
+            manifest.entries[1],
+            MaterialEntry::new(PathBuf::from("b.jpg"))
+        );
+    }
+
+    #[test]
+    fn test_from_csv_with_optional_columns() {
+        let csv = "path,tags,weight,max_usage,focus_x,focus_y,color\n\
+                   a.jpg,crowd; stage,1.5,4,0.5,0.5,#808080\n\
+                   b.jpg,,,,,,\n";
+
+        let manifest = MaterialManifest::from_csv(csv).unwrap();
+
+        assert_eq!(manifest.entries.len(), 2);
+        let a = &manifest.entries[0];
+        assert_eq!(a.tags, vec!["crowd", "stage"]);
+        assert_eq!(a.weight, Some(1.5));
+        assert_eq!(a.max_usage, Some(4));
+        assert_eq!(a.focus_point, Some((0.5, 0.5)));
+        assert!(a.lab_color.unwrap().a.abs() < 1.0);
+
+        assert_eq!(
+            manifest.entries[1],
+            MaterialEntry::new(PathBuf::from("b.jpg"))
+        );
+    }
+
+    #[test]
+    fn test_from_csv_path_only() {
+        let manifest = MaterialManifest::from_csv("path\nx.png\ny.png\n").unwrap();
+        assert_eq!(manifest.entries.len(), 2);
+        assert!(manifest.entries[0].tags.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_entries_are_rejected() {
+        assert!(MaterialManifest::from_json(r#"[{"path": "a.jpg", "weight": 0.0}]"#).is_err());
+        assert!(
+            MaterialManifest::from_json(r#"[{"path": "a.jpg", "focus": [1.5, 0.0]}]"#).is_err()
+        );
+        assert!(MaterialManifest::from_json(r#"[{"path": "a.jpg", "color": "red"}]"#).is_err());
+        assert!(MaterialManifest::from_csv("path,focus_x\na.jpg,0.5\n").is_err());
+    }
+
+    #[test]
+    fn test_load_resolves_relative_paths() {
+        let dir = tempdir().unwrap();
+        let manifest_path = dir.path().join("materials.json");
+        std::fs::write(
+            &manifest_path,
+            r#"[{"path": "photos/a.jpg"}, {"path": "/abs/b.jpg"}]"#,
+        )
+        .unwrap();
+
+        let manifest = MaterialManifest::load(&manifest_path).unwrap();
+
+        assert_eq!(manifest.entries[0].path, dir.path().join("photos/a.jpg"));
+        assert_eq!(manifest.entries[1].path, PathBuf::from("/abs/b.jpg"));
+    }
+
+    #[test]
+    fn test_load_unsupported_extension() {
+        let dir = tempdir().unwrap();
+        let manifest_path = dir.path().join("materials.txt");
+        std::fs::write(&manifest_path, "a.jpg").unwrap();
+
+        assert!(MaterialManifest::load(&manifest_path).is_err());
+    }
+
+    #[test]
+    fn test_apply_to_tile() {
+        let mut entry = MaterialEntry::new(PathBuf::from("a.jpg"));
+        entry.tags = vec!["logo".to_string()];
+        entry.weight = Some(3.0);
+        entry.max_usage = Some(2);
+
+        let mut tile = Tile::new(PathBuf::from("a.jpg"), Lab::new(50.0, 0.0, 0.0), 1.0);
+        entry.apply_to(&mut tile);
+
+        assert!(tile.has_tag("logo"));
+        assert_eq!(tile.weight, 3.0);
+        assert_eq!(tile.max_usage, Some(2));
+        assert_eq!(tile.focus_point, None);
+    }
+}
@@ -1,6 +1,20 @@
 use crate::adjacency::{AdjacencyPenaltyCalculator, GridPosition};
-use rand::Rng;
-use std::path::PathBuf;
+use crate::reuse_distance::{ReuseDistance, ReuseIndex};
+use crate::tile_grid::{TileGrid, TileId};
+use anyhow::{bail, Result};
+use palette::Lab;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt;
+use std::str::FromStr;
+
+/// Materials offered to each cell for replacement moves, nearest in color first
+pub const REPLACEMENT_CANDIDATES: usize = 16;
+
+/// Default cost of one squared Lab unit of color distance when the optimizer weighs color:
+/// a tile ΔE 10 away from its cell's color costs as much as one identical neighbor pair at
+/// adjacency penalty weight 1
+pub const DEFAULT_COLOR_WEIGHT: f32 = 0.01;
 
 /// Configuration for the optimization process
 #[derive(Debug, Clone)]
@@ -13,6 +27,10 @@ pub struct OptimizationConfig {
     pub temperature_decay: f32,
     /// Progress reporting interval
     pub report_interval: usize,
+    /// Seed for choosing moves; `None` uses a fresh random seed on every run
+    pub seed: Option<u64>,
+    /// Kinds of change tried; each iteration picks one of them at random
+    pub moves: MoveSet,
 }
 
 impl Default for OptimizationConfig {
@@ -22,79 +40,371 @@ impl Default for OptimizationConfig {
             initial_temperature: 100.0,
             temperature_decay: 0.99995,
             report_interval: 100,
+            seed: None,
+            moves: MoveSet::default(),
         }
     }
 }
 
+/// A kind of change the optimizer can make to the grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MoveKind {
+    /// Exchange the tiles of two cells
+    Swap,
+    /// Put a material from the cell's candidates that is below its usage cap into one cell
+    Replace,
+    /// Move the tiles of three cells one step around, so the first tile goes to the second
+    /// cell, the second to the third and the third to the first
+    Rotate,
+}
+
+impl fmt::Display for MoveKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Swap => "swap",
+            Self::Replace => "replace",
+            Self::Rotate => "rotate",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Which [`MoveKind`]s the optimizer tries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoveSet {
+    pub swap: bool,
+    pub replace: bool,
+    pub rotate: bool,
+}
+
+impl MoveSet {
+    /// Only swaps, which keep the set of placed materials unchanged
+    pub const SWAP_ONLY: Self = Self {
+        swap: true,
+        replace: false,
+        rotate: false,
+    };
+
+    /// Every kind of move
+    pub const ALL: Self = Self {
+        swap: true,
+        replace: true,
+        rotate: true,
+    };
+
+    /// The enabled moves, in a fixed order
+    pub fn kinds(&self) -> Vec<MoveKind> {
+        [
+            (self.swap, MoveKind::Swap),
+            (self.replace, MoveKind::Replace),
+            (self.rotate, MoveKind::Rotate),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, kind)| enabled.then_some(kind))
+        .collect()
+    }
+}
+
+impl Default for MoveSet {
+    fn default() -> Self {
+        Self::SWAP_ONLY
+    }
+}
+
+impl FromStr for MoveSet {
+    type Err = anyhow::Error;
+
+    /// Parse a comma-separated list such as `swap,rotate`, or `all`
+    fn from_str(s: &str) -> Result<Self> {
+        let mut moves = Self {
+            swap: false,
+            replace: false,
+            rotate: false,
+        };
+        for name in s.split(',').map(str::trim) {
+            match name.to_lowercase().as_str() {
+                "swap" => moves.swap = true,
+                "replace" => moves.replace = true,
+                "rotate" => moves.rotate = true,
+                "all" => moves = Self::ALL,
+                _ => {
+                    bail!("Unknown optimizer move {name:?} (expected swap, replace, rotate or all)")
+                }
+            }
+        }
+        Ok(moves)
+    }
+}
+
+impl fmt::Display for MoveSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<String> = self.kinds().iter().map(MoveKind::to_string).collect();
+        f.write_str(&names.join(","))
+    }
+}
+
+/// Materials the optimizer may bring into each cell with [`MoveKind::Replace`], and the
+/// usage bounds every move has to keep
+///
+/// With a pool the optimizer also weighs color: its cost becomes the adjacency cost at the
+/// calculator's penalty weight plus, for every cell, the squared Lab distance between the
+/// cell's target color and the closest orientation of the material placed there, times the
+/// pool's color weight.
+#[derive(Debug, Clone, Default)]
+pub struct ReplacementPool {
+    width: usize,
+    /// Target color of each cell, row by row
+    targets: Vec<Lab>,
+    /// Materials each cell may switch to, row by row
+    candidates: Vec<Vec<TileId>>,
+    /// Colors of each material's orientations, indexed by tile id
+    colors: Vec<Vec<Lab>>,
+    /// Most cells each material may fill, indexed by tile id
+    max_uses: Vec<usize>,
+    /// Fewest cells every material must keep filling
+    min_uses: usize,
+    /// Cost of one squared Lab unit of color distance
+    color_weight: f32,
+}
+
+impl ReplacementPool {
+    /// A pool for a grid whose cells aim for `targets`, without any candidates yet
+    pub fn new(targets: &[Vec<Lab>]) -> Self {
+        let width = targets.first().map_or(0, Vec::len);
+        Self {
+            width,
+            targets: targets.iter().flatten().copied().collect(),
+            candidates: vec![Vec::new(); width * targets.len()],
+            color_weight: DEFAULT_COLOR_WEIGHT,
+            ..Default::default()
+        }
+    }
+
+    /// Register one orientation of a material; the last registered `max_uses` applies
+    pub fn add_material(&mut self, tile: TileId, lab: Lab, max_uses: usize) {
+        let index = tile.index();
+        if index >= self.colors.len() {
+            self.colors.resize(index + 1, Vec::new());
+            self.max_uses.resize(index + 1, 0);
+        }
+        self.colors[index].push(lab);
+        self.max_uses[index] = max_uses;
+    }
+
+    /// Let the cell at `(x, y)` switch to `candidates`
+    pub fn set_candidates(&mut self, x: usize, y: usize, candidates: Vec<TileId>) {
+        self.candidates[y * self.width + x] = candidates;
+    }
+
+    /// Keep every material in at least `min_uses` cells
+    pub fn with_min_uses(mut self, min_uses: usize) -> Self {
+        self.min_uses = min_uses;
+        self
+    }
+
+    /// Scale the color term against the adjacency cost
+    pub fn with_color_weight(mut self, color_weight: f32) -> Self {
+        self.color_weight = color_weight;
+        self
+    }
+
+    /// Weighted color cost of every placed tile
+    pub fn total_color_cost(&self, grid: &TileGrid) -> f32 {
+        grid.placed()
+            .map(|(position, tile)| self.color_cost(tile, position))
+            .sum()
+    }
+
+    fn candidates(&self, position: GridPosition) -> &[TileId] {
+        &self.candidates[position.y * self.width + position.x]
+    }
+
+    fn max_uses(&self, tile: TileId) -> usize {
+        self.max_uses.get(tile.index()).copied().unwrap_or(0)
+    }
+
+    /// Weighted squared Lab distance from the cell's target to the closest orientation of
+    /// `tile`, or 0.0 for materials outside the pool
+    fn color_cost(&self, tile: TileId, position: GridPosition) -> f32 {
+        let target = self.targets[position.y * self.width + position.x];
+        self.color_weight
+            * self
+                .colors
+                .get(tile.index())
+                .into_iter()
+                .flatten()
+                .map(|lab| {
+                    (lab.l - target.l).powi(2)
+                        + (lab.a - target.a).powi(2)
+                        + (lab.b - target.b).powi(2)
+                })
+                .reduce(f32::min)
+                .unwrap_or(0.0)
+    }
+
+    /// Number of cells each material fills, indexed by tile id
+    fn usage(&self, grid: &TileGrid) -> Vec<usize> {
+        let mut usage = vec![0; self.colors.len()];
+        for (_, tile) in grid.placed() {
+            if let Some(count) = usage.get_mut(tile.index()) {
+                *count += 1;
+            }
+        }
+        usage
+    }
+}
+
+/// A proposed change: the cells it touches, the tile each would take and the cost change
+struct Move {
+    changes: [(GridPosition, TileId); 3],
+    len: usize,
+    delta: f32,
+}
+
+impl Move {
+    fn changes(&self) -> &[(GridPosition, TileId)] {
+        &self.changes[..self.len]
+    }
+}
+
+/// What a run tracks besides the grid, kept in step with every applied move
+struct MoveState {
+    reuse: Option<ReuseIndex>,
+    /// Cells each material fills, when a replacement pool is set
+    usage: Vec<usize>,
+}
+
 /// Performs simulated annealing optimization on the tile placement
 pub struct MosaicOptimizer<'a> {
     calculator: &'a AdjacencyPenaltyCalculator<'a>,
     config: OptimizationConfig,
+    regions: Option<&'a [Vec<Option<usize>>]>,
+    reuse_distance: Option<ReuseDistance>,
+    replacements: Option<&'a ReplacementPool>,
 }
 
 impl<'a> MosaicOptimizer<'a> {
     pub fn new(calculator: &'a AdjacencyPenaltyCalculator<'a>, config: OptimizationConfig) -> Self {
-        Self { calculator, config }
+        Self {
+            calculator,
+            config,
+            regions: None,
+            reuse_distance: None,
+            replacements: None,
+        }
+    }
+
+    /// Restrict moves to cells that belong to the same material region
+    pub fn with_regions(mut self, regions: &'a [Vec<Option<usize>>]) -> Self {
+        self.regions = Some(regions);
+        self
+    }
+
+    /// Reject moves that would bring copies of a material closer than `rule` allows
+    pub fn with_reuse_distance(mut self, rule: ReuseDistance) -> Self {
+        self.reuse_distance = rule.is_enabled().then_some(rule);
+        self
+    }
+
+    /// Enable [`MoveKind::Replace`] with the materials of `pool`, and weigh color in the cost
+    pub fn with_replacements(mut self, pool: &'a ReplacementPool) -> Self {
+        self.replacements = Some(pool);
+        self
+    }
+
+    /// Index of where each material sits, when a reuse distance is enforced
+    fn reuse_index(&self, grid: &TileGrid) -> Option<ReuseIndex> {
+        self.reuse_distance
+            .map(|rule| ReuseIndex::from_grid(rule, grid))
+    }
+
+    /// Random source for move selection, reproducible when the config has a seed
+    fn rng(&self) -> StdRng {
+        match self.config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        }
+    }
+
+    /// Check whether two cells may exchange tiles under the region constraint
+    fn same_region(&self, pos1: GridPosition, pos2: GridPosition) -> bool {
+        match self.regions {
+            Some(regions) => regions[pos1.y][pos1.x] == regions[pos2.y][pos2.x],
+            None => true,
+        }
+    }
+
+    /// Moves to try; replacements need a pool to draw from
+    fn move_kinds(&self) -> Vec<MoveKind> {
+        let mut kinds = self.config.moves.kinds();
+        if self.replacements.is_none() {
+            kinds.retain(|&kind| kind != MoveKind::Replace);
+        }
+        kinds
+    }
+
+    fn start(&self, grid: &TileGrid) -> (MoveState, f32) {
+        let state = MoveState {
+            reuse: self.reuse_index(grid),
+            usage: self
+                .replacements
+                .map(|pool| pool.usage(grid))
+                .unwrap_or_default(),
+        };
+        let cost = match self.replacements {
+            Some(pool) => {
+                self.calculator.penalty_weight() * self.calculator.calculate_total_cost(grid)
+                    + pool.total_color_cost(grid)
+            }
+            None => self.calculator.calculate_total_cost(grid),
+        };
+        (state, cost)
     }
 
     /// Optimize the mosaic placement using simulated annealing
-    pub fn optimize(&self, grid: &mut [Vec<Option<PathBuf>>]) -> OptimizationResult {
-        let grid_height = grid.len();
-        if grid_height == 0 {
+    pub fn optimize(&self, grid: &mut TileGrid) -> OptimizationResult {
+        if grid.is_empty() {
             return OptimizationResult::default();
         }
-        let grid_width = grid[0].len();
+        let kinds = self.move_kinds();
 
-        let mut rng = rand::thread_rng();
-        let mut current_cost = self.calculator.calculate_total_cost(grid);
+        let mut rng = self.rng();
+        let (mut state, mut current_cost) = self.start(grid);
         let initial_cost = current_cost;
+        let mut result = OptimizationResult::default();
         let mut best_cost = current_cost;
-        let mut improved_count = 0;
-        let mut accepted_count = 0;
         let mut temperature = self.config.initial_temperature;
 
         println!("Starting optimization with initial cost: {initial_cost:.3}");
 
         for iteration in 0..self.config.max_iterations {
-            // Select two random positions
-            let pos1 =
-                GridPosition::new(rng.gen_range(0..grid_width), rng.gen_range(0..grid_height));
-            let pos2 =
-                GridPosition::new(rng.gen_range(0..grid_width), rng.gen_range(0..grid_height));
-
-            // Skip if same position
-            if pos1 == pos2 {
-                continue;
-            }
-
-            // Skip if either position is empty
-            if grid[pos1.y][pos1.x].is_none() || grid[pos2.y][pos2.x].is_none() {
-                continue;
-            }
-
-            // Calculate the change in cost if we swap
-            let delta = self.calculator.calculate_swap_delta(grid, pos1, pos2);
-
-            // Simulated annealing acceptance criterion
-            let accept = if delta < 0.0 {
-                true
-            } else {
-                let probability = (-delta / temperature).exp();
-                rng.gen::<f32>() < probability
+            let kind = match kinds.len() {
+                0 => break,
+                1 => kinds[0],
+                n => kinds[rng.gen_range(0..n)],
             };
 
-            if accept {
-                // Perform the swap
-                let temp = grid[pos1.y][pos1.x].clone();
-                grid[pos1.y][pos1.x] = grid[pos2.y][pos2.x].clone();
-                grid[pos2.y][pos2.x] = temp;
-
-                current_cost += delta;
-                accepted_count += 1;
-
-                if current_cost < best_cost {
-                    best_cost = current_cost;
-                    improved_count += 1;
+            if let Some(proposal) = self.propose(kind, grid, &mut state, &mut rng) {
+                result.stats_mut(kind).proposed += 1;
+
+                // Simulated annealing acceptance criterion
+                let accept = if proposal.delta < 0.0 {
+                    true
+                } else {
+                    let probability = (-proposal.delta / temperature).exp();
+                    rng.gen::<f32>() < probability
+                };
+
+                if accept {
+                    self.apply(&proposal, grid, &mut state);
+                    current_cost += proposal.delta;
+                    result.stats_mut(kind).accepted += 1;
+                    result.accepted_count += 1;
+
+                    if current_cost < best_cost {
+                        best_cost = current_cost;
+                        result.improved_count += 1;
+                    }
                 }
             }
 
@@ -108,69 +418,59 @@ impl<'a> MosaicOptimizer<'a> {
                     iteration + 1,
                     current_cost,
                     temperature,
-                    improved_count,
-                    accepted_count
+                    result.improved_count,
+                    result.accepted_count
                 );
             }
         }
 
         println!(
-            "Optimization complete: final cost={current_cost:.3}, improvements={improved_count}, accepted={accepted_count}"
+            "Optimization complete: final cost={current_cost:.3}, improvements={}, accepted={}",
+            result.improved_count, result.accepted_count
         );
 
         OptimizationResult {
             initial_cost,
             final_cost: current_cost,
             best_cost,
-            improved_count,
-            accepted_count,
             iterations: self.config.max_iterations,
+            ..result
         }
     }
 
     /// Perform a greedy optimization (only accept improvements)
     pub fn optimize_greedy(
         &self,
-        grid: &mut [Vec<Option<PathBuf>>],
+        grid: &mut TileGrid,
         max_iterations: usize,
     ) -> OptimizationResult {
-        let grid_height = grid.len();
-        if grid_height == 0 {
+        if grid.is_empty() {
             return OptimizationResult::default();
         }
-        let grid_width = grid[0].len();
+        let kinds = self.move_kinds();
 
-        let mut rng = rand::thread_rng();
-        let mut current_cost = self.calculator.calculate_total_cost(grid);
+        let mut rng = self.rng();
+        let (mut state, mut current_cost) = self.start(grid);
         let initial_cost = current_cost;
-        let mut improved_count = 0;
+        let mut result = OptimizationResult::default();
 
         println!("Starting greedy optimization with initial cost: {initial_cost:.3}");
 
         for iteration in 0..max_iterations {
-            let pos1 =
-                GridPosition::new(rng.gen_range(0..grid_width), rng.gen_range(0..grid_height));
-            let pos2 =
-                GridPosition::new(rng.gen_range(0..grid_width), rng.gen_range(0..grid_height));
-
-            if pos1 == pos2 {
-                continue;
-            }
-
-            if grid[pos1.y][pos1.x].is_none() || grid[pos2.y][pos2.x].is_none() {
-                continue;
-            }
-
-            let delta = self.calculator.calculate_swap_delta(grid, pos1, pos2);
-
-            if delta < 0.0 {
-                // Perform the swap
-                let temp = grid[pos1.y][pos1.x].clone();
-                grid[pos1.y][pos1.x] = grid[pos2.y][pos2.x].clone();
-                grid[pos2.y][pos2.x] = temp;
+            let kind = match kinds.len() {
+                0 => break,
+                1 => kinds[0],
+                n => kinds[rng.gen_range(0..n)],
+            };
 
-                current_cost += delta;
-                improved_count += 1;
+            if let Some(proposal) = self.propose(kind, grid, &mut state, &mut rng) {
+                result.stats_mut(kind).proposed += 1;
+                if proposal.delta < 0.0 {
+                    self.apply(&proposal, grid, &mut state);
+                    current_cost += proposal.delta;
+                    result.stats_mut(kind).accepted += 1;
+                    result.improved_count += 1;
+                }
             }
 
             if (iteration + 1) % 100 == 0 {
@@ -178,26 +478,252 @@ impl<'a> MosaicOptimizer<'a> {
                     "Iteration {}: cost={:.3}, improvements={}",
                     iteration + 1,
                     current_cost,
-                    improved_count
+                    result.improved_count
                 );
             }
         }
 
         println!(
-            "Greedy optimization complete: final cost={current_cost:.3}, improvements={improved_count}"
+            "Greedy optimization complete: final cost={current_cost:.3}, improvements={}",
+            result.improved_count
         );
 
         OptimizationResult {
             initial_cost,
             final_cost: current_cost,
             best_cost: current_cost,
-            improved_count,
-            accepted_count: improved_count,
+            accepted_count: result.improved_count,
             iterations: max_iterations,
+            ..result
+        }
+    }
+
+    /// Draw a random move of `kind`, or `None` if the drawn cells cannot take it
+    fn propose(
+        &self,
+        kind: MoveKind,
+        grid: &TileGrid,
+        state: &mut MoveState,
+        rng: &mut StdRng,
+    ) -> Option<Move> {
+        let (width, height) = (grid.width(), grid.height());
+        let mut cell = || GridPosition::new(rng.gen_range(0..width), rng.gen_range(0..height));
+
+        match kind {
+            MoveKind::Swap => {
+                let (pos1, pos2) = (cell(), cell());
+                if pos1 == pos2 || !self.same_region(pos1, pos2) {
+                    return None;
+                }
+                let (tile1, tile2) = (grid.get(pos1.x, pos1.y)?, grid.get(pos2.x, pos2.y)?);
+                // Skip swaps that would bring a tile too close to its copies
+                if !state
+                    .reuse
+                    .as_ref()
+                    .is_none_or(|index| swap_keeps_reuse_distance(index, grid, pos1, pos2))
+                {
+                    return None;
+                }
+                let adjacency = self.calculator.calculate_swap_delta(grid, pos1, pos2);
+                Some(self.scored(grid, &[(pos1, tile2), (pos2, tile1)], adjacency))
+            }
+            MoveKind::Replace => {
+                let pool = self.replacements?;
+                let position = cell();
+                let old = grid.get(position.x, position.y)?;
+                let candidates = pool.candidates(position);
+                if candidates.is_empty() {
+                    return None;
+                }
+                let new = candidates[rng.gen_range(0..candidates.len())];
+                if new == old
+                    || state.usage.get(new.index()).copied().unwrap_or(0) >= pool.max_uses(new)
+                    || state.usage.get(old.index()).copied().unwrap_or(0) <= pool.min_uses
+                    || !state
+                        .reuse
+                        .as_ref()
+                        .is_none_or(|index| index.allows(new, position.x, position.y))
+                {
+                    return None;
+                }
+                let changes = [(position, new)];
+                let adjacency = self.calculator.calculate_change_delta(grid, &changes);
+                Some(self.scored(grid, &changes, adjacency))
+            }
+            MoveKind::Rotate => {
+                let cells = [cell(), cell(), cell()];
+                if cells[0] == cells[1] || cells[1] == cells[2] || cells[0] == cells[2] {
+                    return None;
+                }
+                if !self.same_region(cells[0], cells[1]) || !self.same_region(cells[0], cells[2]) {
+                    return None;
+                }
+                let tiles = [
+                    grid.get(cells[0].x, cells[0].y)?,
+                    grid.get(cells[1].x, cells[1].y)?,
+                    grid.get(cells[2].x, cells[2].y)?,
+                ];
+                if tiles[0] == tiles[1] && tiles[1] == tiles[2] {
+                    return None;
+                }
+                // Each cell takes the tile of the cell before it
+                let changes = [
+                    (cells[0], tiles[2]),
+                    (cells[1], tiles[0]),
+                    (cells[2], tiles[1]),
+                ];
+                if let Some(index) = state.reuse.as_mut() {
+                    if !changes_keep_reuse_distance(index, grid, &changes) {
+                        return None;
+                    }
+                }
+                let adjacency = self.calculator.calculate_change_delta(grid, &changes);
+                Some(self.scored(grid, &changes, adjacency))
+            }
+        }
+    }
+
+    /// Wrap `changes` into a move, adding their color change to the adjacency change when
+    /// a replacement pool is set
+    fn scored(&self, grid: &TileGrid, changes: &[(GridPosition, TileId)], adjacency: f32) -> Move {
+        let delta = match self.replacements {
+            Some(pool) => {
+                let color: f32 = changes
+                    .iter()
+                    .map(|&(position, new)| {
+                        let old = grid.get(position.x, position.y);
+                        pool.color_cost(new, position)
+                            - old.map_or(0.0, |old| pool.color_cost(old, position))
+                    })
+                    .sum();
+                self.calculator.penalty_weight() * adjacency + color
+            }
+            None => adjacency,
+        };
+        let mut cells = [changes[0]; 3];
+        cells[..changes.len()].copy_from_slice(changes);
+        Move {
+            changes: cells,
+            len: changes.len(),
+            delta,
+        }
+    }
+
+    fn apply(&self, proposal: &Move, grid: &mut TileGrid, state: &mut MoveState) {
+        let changes = proposal.changes();
+        if let Some(index) = state.reuse.as_mut() {
+            apply_to_reuse_index(index, grid, changes);
+        }
+        if !state.usage.is_empty() {
+            for &(position, new) in changes {
+                if let Some(old) = grid.get(position.x, position.y) {
+                    state.usage[old.index()] -= 1;
+                }
+                if let Some(count) = state.usage.get_mut(new.index()) {
+                    *count += 1;
+                }
+            }
+        }
+        for &(position, new) in changes {
+            grid.set(position.x, position.y, Some(new));
+        }
+    }
+}
+
+/// Whether swapping the tiles at `pos1` and `pos2` keeps both at the reuse distance from
+/// their other copies
+fn swap_keeps_reuse_distance(
+    index: &ReuseIndex,
+    grid: &TileGrid,
+    pos1: GridPosition,
+    pos2: GridPosition,
+) -> bool {
+    let (Some(first), Some(second)) = (grid.get(pos1.x, pos1.y), grid.get(pos2.x, pos2.y)) else {
+        return true;
+    };
+    // Swapping two copies of one material changes nothing
+    first == second
+        || (index.allows_move(first, (pos1.x, pos1.y), (pos2.x, pos2.y))
+            && index.allows_move(second, (pos2.x, pos2.y), (pos1.x, pos1.y)))
+}
+
+/// Whether every cell of `changes` may take its new tile at once under the reuse distance
+///
+/// Tries the change on the index and restores it afterwards.
+fn changes_keep_reuse_distance(
+    index: &mut ReuseIndex,
+    grid: &TileGrid,
+    changes: &[(GridPosition, TileId)],
+) -> bool {
+    let old = |position: GridPosition| grid.get(position.x, position.y);
+    for &(position, _) in changes {
+        if let Some(tile) = old(position) {
+            index.remove(tile, position.x, position.y);
+        }
+    }
+    let mut inserted = 0;
+    for &(position, new) in changes {
+        if !index.allows(new, position.x, position.y) {
+            break;
+        }
+        index.insert(new, position.x, position.y);
+        inserted += 1;
+    }
+    for &(position, new) in &changes[..inserted] {
+        index.remove(new, position.x, position.y);
+    }
+    for &(position, _) in changes {
+        if let Some(tile) = old(position) {
+            index.insert(tile, position.x, position.y);
+        }
+    }
+    inserted == changes.len()
+}
+
+/// Update the index for `changes`, before they are applied to the grid
+fn apply_to_reuse_index(
+    index: &mut ReuseIndex,
+    grid: &TileGrid,
+    changes: &[(GridPosition, TileId)],
+) {
+    for &(position, _) in changes {
+        if let Some(tile) = grid.get(position.x, position.y) {
+            index.remove(tile, position.x, position.y);
+        }
+    }
+    for &(position, new) in changes {
+        index.insert(new, position.x, position.y);
+    }
+}
+
+/// How often the optimizer tried and kept one kind of move
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MoveStats {
+    /// Moves that met every constraint and were scored
+    pub proposed: usize,
+    pub accepted: usize,
+}
+
+impl MoveStats {
+    /// Share of proposed moves that were accepted, in percent
+    pub fn acceptance_percentage(&self) -> f32 {
+        if self.proposed > 0 {
+            self.accepted as f32 / self.proposed as f32 * 100.0
+        } else {
+            0.0
         }
     }
 }
 
+/// How much lower `final_cost` is than `initial_cost`, in percent
+pub fn improvement_percentage(initial_cost: f32, final_cost: f32) -> f32 {
+    if initial_cost > 0.0 {
+        ((initial_cost - final_cost) / initial_cost) * 100.0
+    } else {
+        0.0
+    }
+}
+
 /// Results from the optimization process
 #[derive(Debug, Default)]
 pub struct OptimizationResult {
@@ -207,15 +733,31 @@ pub struct OptimizationResult {
     pub improved_count: usize,
     pub accepted_count: usize,
     pub iterations: usize,
+    pub swaps: MoveStats,
+    pub replacements: MoveStats,
+    pub rotations: MoveStats,
 }
 
 impl OptimizationResult {
     /// Calculate the improvement percentage
     pub fn improvement_percentage(&self) -> f32 {
-        if self.initial_cost > 0.0 {
-            ((self.initial_cost - self.final_cost) / self.initial_cost) * 100.0
-        } else {
-            0.0
+        improvement_percentage(self.initial_cost, self.final_cost)
+    }
+
+    /// Statistics of one kind of move
+    pub fn move_stats(&self, kind: MoveKind) -> MoveStats {
+        match kind {
+            MoveKind::Swap => self.swaps,
+            MoveKind::Replace => self.replacements,
+            MoveKind::Rotate => self.rotations,
+        }
+    }
+
+    fn stats_mut(&mut self, kind: MoveKind) -> &mut MoveStats {
+        match kind {
+            MoveKind::Swap => &mut self.swaps,
+            MoveKind::Replace => &mut self.replacements,
+            MoveKind::Rotate => &mut self.rotations,
         }
     }
 }
@@ -225,9 +767,17 @@ mod tests {
     use super::*;
     use crate::adjacency::AdjacencyPenaltyCalculator;
     use crate::similarity::SimilarityDatabase;
+    use crate::tile_grid::TileId;
     use palette::Lab;
+    use std::collections::HashSet;
+    use std::path::{Path, PathBuf};
 
-    fn create_test_grid() -> (Vec<Vec<Option<PathBuf>>>, SimilarityDatabase) {
+    /// Id of a tile added to `sim_db`
+    fn id(sim_db: &SimilarityDatabase, name: &str) -> TileId {
+        sim_db.tile_id(Path::new(name)).unwrap()
+    }
+
+    fn create_test_grid() -> (TileGrid, SimilarityDatabase) {
         let mut sim_db = SimilarityDatabase::new();
         sim_db.add_tile(PathBuf::from("tile1.png"), Lab::new(50.0, 0.0, 0.0));
         sim_db.add_tile(PathBuf::from("tile2.png"), Lab::new(60.0, 10.0, 10.0));
@@ -235,11 +785,11 @@ mod tests {
         sim_db.add_tile(PathBuf::from("tile4.png"), Lab::new(55.0, 5.0, 5.0));
         sim_db.build_similarities();
 
-        let mut grid = vec![vec![None; 2]; 2];
-        grid[0][0] = Some(PathBuf::from("tile1.png"));
-        grid[0][1] = Some(PathBuf::from("tile2.png"));
-        grid[1][0] = Some(PathBuf::from("tile3.png"));
-        grid[1][1] = Some(PathBuf::from("tile4.png"));
+        let mut grid = TileGrid::new(2, 2);
+        grid.set(0, 0, Some(id(&sim_db, "tile1.png")));
+        grid.set(1, 0, Some(id(&sim_db, "tile2.png")));
+        grid.set(0, 1, Some(id(&sim_db, "tile3.png")));
+        grid.set(1, 1, Some(id(&sim_db, "tile4.png")));
 
         (grid, sim_db)
     }
@@ -263,6 +813,234 @@ mod tests {
         assert!(result.final_cost >= 0.0);
     }
 
+    #[test]
+    fn test_seeded_optimization_is_reproducible() {
+        let (grid, sim_db) = create_test_grid();
+        let calculator = AdjacencyPenaltyCalculator::new(&sim_db, 1.0);
+        let run = |seed| {
+            let config = OptimizationConfig {
+                max_iterations: 50,
+                seed: Some(seed),
+                ..Default::default()
+            };
+            let mut grid = grid.clone();
+            MosaicOptimizer::new(&calculator, config).optimize(&mut grid);
+            grid
+        };
+
+        assert_eq!(run(7), run(7));
+        let mut greedy_grids = (0..2).map(|_| {
+            let config = OptimizationConfig {
+                seed: Some(7),
+                ..Default::default()
+            };
+            let mut grid = grid.clone();
+            MosaicOptimizer::new(&calculator, config).optimize_greedy(&mut grid, 50);
+            grid
+        });
+        assert_eq!(greedy_grids.next(), greedy_grids.next());
+    }
+
+    #[test]
+    fn test_optimization_keeps_tiles_in_their_region() {
+        let (mut grid, sim_db) = create_test_grid();
+        let original = grid.clone();
+        let calculator = AdjacencyPenaltyCalculator::new(&sim_db, 1.0);
+
+        // Every cell is its own region, so no swap is allowed
+        let regions = vec![vec![Some(0), Some(1)], vec![Some(2), None]];
+        let config = OptimizationConfig {
+            max_iterations: 200,
+            initial_temperature: 1000.0,
+            ..Default::default()
+        };
+
+        let optimizer = MosaicOptimizer::new(&calculator, config).with_regions(&regions);
+        let result = optimizer.optimize(&mut grid);
+        optimizer.optimize_greedy(&mut grid, 200);
+
+        assert_eq!(grid, original);
+        assert_eq!(result.accepted_count, 0);
+    }
+
+    #[test]
+    fn test_optimization_respects_reuse_distance() {
+        use crate::reuse_distance::ReuseMetric;
+
+        let mut sim_db = SimilarityDatabase::new();
+        for i in 0..13 {
+            let path = PathBuf::from(format!("tile{i}.png"));
+            sim_db.add_tile(path, Lab::new(7.0 * i as f32, 0.0, 0.0));
+        }
+        sim_db.build_similarities();
+
+        // Four copies of tile0 in the corners of a 4x4 grid, the only cells where they
+        // are all 3 apart
+        let corners = [(0, 0), (3, 0), (0, 3), (3, 3)];
+        let mut next = 1;
+        let mut grid = TileGrid::from_rows(
+            (0..4)
+                .map(|y| {
+                    (0..4)
+                        .map(|x| {
+                            let i = if corners.contains(&(x, y)) {
+                                0
+                            } else {
+                                next += 1;
+                                next - 1
+                            };
+                            Some(id(&sim_db, &format!("tile{i}.png")))
+                        })
+                        .collect()
+                })
+                .collect(),
+        );
+        let rule = ReuseDistance {
+            min_distance: 3.0,
+            metric: ReuseMetric::Chebyshev,
+        };
+
+        let calculator = AdjacencyPenaltyCalculator::new(&sim_db, 1.0);
+        let config = OptimizationConfig {
+            max_iterations: 2000,
+            initial_temperature: 1000.0,
+            seed: Some(3),
+            ..Default::default()
+        };
+        let optimizer = MosaicOptimizer::new(&calculator, config).with_reuse_distance(rule);
+        let result = optimizer.optimize(&mut grid);
+        optimizer.optimize_greedy(&mut grid, 500);
+
+        assert!(result.accepted_count > 0);
+        for (x, y) in corners {
+            assert_eq!(grid.get(x, y), Some(id(&sim_db, "tile0.png")));
+        }
+    }
+
+    #[test]
+    fn test_move_set_parsing() {
+        let moves: MoveSet = "swap, Rotate".parse().unwrap();
+        assert_eq!(moves.kinds(), [MoveKind::Swap, MoveKind::Rotate]);
+        assert_eq!(moves.to_string(), "swap,rotate");
+        assert_eq!("all".parse::<MoveSet>().unwrap(), MoveSet::ALL);
+        assert_eq!(MoveSet::ALL.to_string(), "swap,replace,rotate");
+        assert_eq!(MoveSet::default(), MoveSet::SWAP_ONLY);
+        assert!("swap,jump".parse::<MoveSet>().is_err());
+    }
+
+    #[test]
+    fn test_rotations_keep_the_placed_tiles() {
+        let (mut grid, sim_db) = create_test_grid();
+        let mut before: Vec<TileId> = grid.placed().map(|(_, tile)| tile).collect();
+        let calculator = AdjacencyPenaltyCalculator::new(&sim_db, 1.0);
+        let config = OptimizationConfig {
+            max_iterations: 200,
+            initial_temperature: 1000.0,
+            seed: Some(5),
+            moves: "rotate".parse().unwrap(),
+            ..Default::default()
+        };
+
+        let optimizer = MosaicOptimizer::new(&calculator, config);
+        let result = optimizer.optimize(&mut grid);
+
+        assert!(result.rotations.accepted > 0);
+        assert_eq!(result.swaps, MoveStats::default());
+        assert_eq!(result.replacements, MoveStats::default());
+        assert!((result.final_cost - calculator.calculate_total_cost(&grid)).abs() < 1e-3);
+        let mut after: Vec<TileId> = grid.placed().map(|(_, tile)| tile).collect();
+        before.sort();
+        after.sort();
+        assert_eq!(before, after);
+    }
+
+    /// Grey materials at the given lightness, in a database and a pool aiming every cell of
+    /// a `width` x 1 grid at lightness 20
+    fn grey_pool(
+        lightness: &[f32],
+        width: usize,
+        max_uses: usize,
+    ) -> (SimilarityDatabase, ReplacementPool) {
+        let mut sim_db = SimilarityDatabase::new();
+        for (i, &l) in lightness.iter().enumerate() {
+            sim_db.add_tile(PathBuf::from(format!("grey{i}.png")), Lab::new(l, 0.0, 0.0));
+        }
+        sim_db.build_similarities();
+
+        let mut pool = ReplacementPool::new(&[vec![Lab::new(20.0, 0.0, 0.0); width]]);
+        for (i, &l) in lightness.iter().enumerate() {
+            pool.add_material(TileId::new(i), Lab::new(l, 0.0, 0.0), max_uses);
+        }
+        let all: Vec<TileId> = (0..lightness.len()).map(TileId::new).collect();
+        for x in 0..width {
+            pool.set_candidates(x, 0, all.clone());
+        }
+        (sim_db, pool)
+    }
+
+    #[test]
+    fn test_replacement_brings_in_unused_materials() {
+        // grey0 and grey1 fill the grid; grey2 matches the target but was never placed
+        let (sim_db, pool) = grey_pool(&[80.0, 60.0, 20.0], 2, 1);
+        let mut grid = TileGrid::from_rows(vec![vec![Some(TileId::new(0)), Some(TileId::new(1))]]);
+        let calculator = AdjacencyPenaltyCalculator::new(&sim_db, 0.3);
+        let config = OptimizationConfig {
+            seed: Some(1),
+            moves: "replace".parse().unwrap(),
+            ..Default::default()
+        };
+
+        let optimizer = MosaicOptimizer::new(&calculator, config).with_replacements(&pool);
+        let result = optimizer.optimize_greedy(&mut grid, 100);
+
+        // grey2 may appear once, so it replaces the worse match and grey1 stays
+        assert_eq!(
+            grid,
+            TileGrid::from_rows(vec![vec![Some(TileId::new(2)), Some(TileId::new(1))]])
+        );
+        assert_eq!(result.replacements.accepted, 1);
+        assert!(result.replacements.proposed > 1);
+        assert!((result.final_cost - optimizer.start(&grid).1).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_color_weight_scales_color_cost() {
+        let (_, pool) = grey_pool(&[80.0, 60.0, 20.0], 2, 1);
+        let grid = TileGrid::from_rows(vec![vec![Some(TileId::new(0)), Some(TileId::new(1))]]);
+
+        // Squared distances to lightness 20 are 3600 and 1600
+        assert!((pool.total_color_cost(&grid) - 5200.0 * DEFAULT_COLOR_WEIGHT).abs() < 1e-3);
+        let pool = pool.with_color_weight(0.5);
+        assert!((pool.total_color_cost(&grid) - 2600.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_replacement_keeps_every_material_placed() {
+        let (sim_db, pool) = grey_pool(&[80.0, 60.0, 20.0], 2, 2);
+        let pool = pool.with_min_uses(1);
+        let mut grid = TileGrid::from_rows(vec![vec![Some(TileId::new(0)), Some(TileId::new(1))]]);
+        let original = grid.clone();
+        let calculator = AdjacencyPenaltyCalculator::new(&sim_db, 0.3);
+        let config = OptimizationConfig {
+            seed: Some(1),
+            moves: MoveSet::ALL,
+            ..Default::default()
+        };
+
+        // Replacing either tile would leave its material unused
+        let optimizer = MosaicOptimizer::new(&calculator, config).with_replacements(&pool);
+        let result = optimizer.optimize_greedy(&mut grid, 100);
+
+        assert_eq!(result.replacements.proposed, 0);
+        assert_eq!(
+            grid.placed().map(|(_, tile)| tile).collect::<HashSet<_>>(),
+            original
+                .placed()
+                .map(|(_, tile)| tile)
+                .collect::<HashSet<_>>()
+        );
+    }
+
     #[test]
     fn test_greedy_optimization() {
         let (mut grid, sim_db) = create_test_grid();
@@ -285,6 +1063,7 @@ mod tests {
             improved_count: 10,
             accepted_count: 15,
             iterations: 100,
+            ..Default::default()
         };
 
         assert_eq!(result.improvement_percentage(), 25.0);
@@ -293,7 +1072,7 @@ mod tests {
     // Edge Case Tests
     #[test]
     fn test_optimization_empty_grid() {
-        let empty_grid: Vec<Vec<Option<PathBuf>>> = vec![];
+        let empty_grid = TileGrid::default();
         let sim_db = SimilarityDatabase::new();
         let calculator = AdjacencyPenaltyCalculator::new(&sim_db, 1.0);
 
@@ -312,7 +1091,7 @@ mod tests {
         sim_db.add_tile(PathBuf::from("single.png"), Lab::new(50.0, 0.0, 0.0));
         sim_db.build_similarities();
 
-        let mut grid = vec![vec![Some(PathBuf::from("single.png"))]];
+        let mut grid = TileGrid::from_rows(vec![vec![Some(id(&sim_db, "single.png"))]]);
         let calculator = AdjacencyPenaltyCalculator::new(&sim_db, 1.0);
 
         let config = OptimizationConfig {
@@ -337,9 +1116,9 @@ mod tests {
         sim_db.add_tile(PathBuf::from("tile2.png"), Lab::new(60.0, 10.0, 10.0));
         sim_db.build_similarities();
 
-        let mut grid = vec![vec![None; 3]; 3];
-        grid[0][0] = Some(PathBuf::from("tile1.png"));
-        grid[2][2] = Some(PathBuf::from("tile2.png"));
+        let mut grid = TileGrid::new(3, 3);
+        grid.set(0, 0, Some(id(&sim_db, "tile1.png")));
+        grid.set(2, 2, Some(id(&sim_db, "tile2.png")));
         // Rest remain None
 
         let calculator = AdjacencyPenaltyCalculator::new(&sim_db, 1.0);
@@ -492,12 +1271,10 @@ mod tests {
         sim_db.build_similarities();
 
         // Create 5x5 grid
-        let mut grid = vec![vec![None; 5]; 5];
-        let mut tile_idx = 0;
+        let mut grid = TileGrid::new(5, 5);
         for y in 0..5 {
             for x in 0..5 {
-                grid[y][x] = Some(PathBuf::from(format!("tile_{}.png", tile_idx)));
-                tile_idx += 1;
+                grid.set(x, y, Some(id(&sim_db, &format!("tile_{}.png", y * 5 + x))));
             }
         }
 
@@ -520,7 +1297,7 @@ mod tests {
     #[test]
     fn test_greedy_optimization_edge_cases() {
         // Test greedy optimization with empty grid
-        let empty_grid: Vec<Vec<Option<PathBuf>>> = vec![];
+        let empty_grid = TileGrid::default();
         let sim_db = SimilarityDatabase::new();
         let calculator = AdjacencyPenaltyCalculator::new(&sim_db, 1.0);
 
@@ -556,6 +1333,7 @@ mod tests {
             improved_count: 0,
             accepted_count: 0,
             iterations: 10,
+            ..Default::default()
         };
 
         assert_eq!(result_zero.improvement_percentage(), 0.0);
@@ -568,6 +1346,7 @@ mod tests {
             improved_count: 0,
             accepted_count: 5,
             iterations: 100,
+            ..Default::default()
         };
 
         assert_eq!(result_worse.improvement_percentage(), -50.0);
@@ -580,6 +1359,7 @@ mod tests {
             improved_count: 50,
             accepted_count: 60,
             iterations: 200,
+            ..Default::default()
         };
 
         assert_eq!(result_perfect.improvement_percentage(), 100.0);
@@ -597,11 +1377,11 @@ mod tests {
         sim_db.add_tile(PathBuf::from("identical4.png"), identical_color);
         sim_db.build_similarities();
 
-        let mut grid = vec![vec![None; 2]; 2];
-        grid[0][0] = Some(PathBuf::from("identical1.png"));
-        grid[0][1] = Some(PathBuf::from("identical2.png"));
-        grid[1][0] = Some(PathBuf::from("identical3.png"));
-        grid[1][1] = Some(PathBuf::from("identical4.png"));
+        let mut grid = TileGrid::new(2, 2);
+        grid.set(0, 0, Some(id(&sim_db, "identical1.png")));
+        grid.set(1, 0, Some(id(&sim_db, "identical2.png")));
+        grid.set(0, 1, Some(id(&sim_db, "identical3.png")));
+        grid.set(1, 1, Some(id(&sim_db, "identical4.png")));
 
         let calculator = AdjacencyPenaltyCalculator::new(&sim_db, 1.0);
         let config = OptimizationConfig {
@@ -638,11 +1418,11 @@ mod tests {
         sim_db.add_tile(PathBuf::from("extreme4.png"), Lab::new(25.0, -50.0, 50.0));
         sim_db.build_similarities();
 
-        let mut grid = vec![vec![None; 2]; 2];
-        grid[0][0] = Some(PathBuf::from("extreme1.png"));
-        grid[0][1] = Some(PathBuf::from("extreme2.png"));
-        grid[1][0] = Some(PathBuf::from("extreme3.png"));
-        grid[1][1] = Some(PathBuf::from("extreme4.png"));
+        let mut grid = TileGrid::new(2, 2);
+        grid.set(0, 0, Some(id(&sim_db, "extreme1.png")));
+        grid.set(1, 0, Some(id(&sim_db, "extreme2.png")));
+        grid.set(0, 1, Some(id(&sim_db, "extreme3.png")));
+        grid.set(1, 1, Some(id(&sim_db, "extreme4.png")));
 
         let calculator = AdjacencyPenaltyCalculator::new(&sim_db, 2.0);
         let config = OptimizationConfig {
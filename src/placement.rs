@@ -0,0 +1,185 @@
+use anyhow::{bail, Result};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::fmt;
+use std::str::FromStr;
+
+/// Order in which the greedy matcher fills grid cells
+///
+/// Earlier cells get first pick of tiles before usage limits and adjacency rules narrow
+/// the choice, so the order decides where the compromises end up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlacementOrder {
+    /// Row by row, left to right
+    #[default]
+    Raster,
+    /// Row by row, alternating direction so consecutive cells are always neighbors
+    Serpentine,
+    /// Seeded shuffle, spreading compromises evenly over the image
+    Random,
+    /// Outward from the center in a square spiral
+    Spiral,
+    /// Cells whose closest tile is farthest away first
+    HardestFirst,
+}
+
+impl PlacementOrder {
+    /// All cells of a `grid_width` x `grid_height` grid as `(x, y)`, in this order
+    ///
+    /// `difficulty` holds each cell's distance to its closest tile and is only used by
+    /// [`PlacementOrder::HardestFirst`], which falls back to raster order without it.
+    /// Equally difficult cells keep their raster order.
+    pub fn cells(
+        &self,
+        grid_width: usize,
+        grid_height: usize,
+        seed: u64,
+        difficulty: Option<&[Vec<f32>]>,
+    ) -> Vec<(usize, usize)> {
+        let mut cells: Vec<(usize, usize)> = (0..grid_height)
+            .flat_map(|y| (0..grid_width).map(move |x| (x, y)))
+            .collect();
+
+        match self {
+            Self::Raster => {}
+            Self::Serpentine => {
+                for row in cells.chunks_mut(grid_width.max(1)).skip(1).step_by(2) {
+                    row.reverse();
+                }
+            }
+            Self::Random => cells.shuffle(&mut StdRng::seed_from_u64(seed)),
+            Self::Spiral => cells = spiral(grid_width, grid_height),
+            Self::HardestFirst => {
+                if let Some(difficulty) = difficulty {
+                    cells.sort_by(|&(ax, ay), &(bx, by)| {
+                        difficulty[by][bx].total_cmp(&difficulty[ay][ax])
+                    });
+                }
+            }
+        }
+        cells
+    }
+}
+
+/// Walk a square spiral from the center cell, keeping the cells inside the grid
+fn spiral(grid_width: usize, grid_height: usize) -> Vec<(usize, usize)> {
+    let total = grid_width * grid_height;
+    let mut cells = Vec::with_capacity(total);
+    if total == 0 {
+        return cells;
+    }
+
+    let (mut x, mut y) = (
+        ((grid_width - 1) / 2) as i64,
+        ((grid_height - 1) / 2) as i64,
+    );
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let visit = |x: i64, y: i64, cells: &mut Vec<(usize, usize)>| {
+        if (0..grid_width as i64).contains(&x) && (0..grid_height as i64).contains(&y) {
+            cells.push((x as usize, y as usize));
+        }
+    };
+
+    visit(x, y, &mut cells);
+    // Leg lengths go 1, 1, 2, 2, 3, 3, ...
+    let mut leg = 0;
+    while cells.len() < total {
+        let (dx, dy) = directions[leg % 4];
+        for _ in 0..leg / 2 + 1 {
+            x += dx;
+            y += dy;
+            visit(x, y, &mut cells);
+        }
+        leg += 1;
+    }
+    cells
+}
+
+impl FromStr for PlacementOrder {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "raster" => Ok(Self::Raster),
+            "serpentine" => Ok(Self::Serpentine),
+            "random" => Ok(Self::Random),
+            "spiral" => Ok(Self::Spiral),
+            "hardest" | "hardest-first" => Ok(Self::HardestFirst),
+            _ => bail!(
+                "Unknown placement order {s:?} (expected raster, serpentine, random, spiral or hardest)"
+            ),
+        }
+    }
+}
+
+impl fmt::Display for PlacementOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Raster => "raster",
+            Self::Serpentine => "serpentine",
+            Self::Random => "random",
+            Self::Spiral => "spiral",
+            Self::HardestFirst => "hardest",
+        };
+        f.write_str(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORDERS: [PlacementOrder; 5] = [
+        PlacementOrder::Raster,
+        PlacementOrder::Serpentine,
+        PlacementOrder::Random,
+        PlacementOrder::Spiral,
+        PlacementOrder::HardestFirst,
+    ];
+
+    #[test]
+    fn test_every_order_visits_each_cell_once() {
+        for (width, height) in [(1, 1), (4, 3), (3, 6), (7, 2)] {
+            for order in ORDERS {
+                let mut cells = order.cells(width, height, 9, None);
+                cells.sort_unstable();
+                cells.dedup();
+                assert_eq!(cells.len(), width * height, "{order} {width}x{height}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_serpentine_and_spiral_move_between_neighbors() {
+        let serpentine = PlacementOrder::Serpentine.cells(3, 2, 0, None);
+        assert_eq!(serpentine, [(0, 0), (1, 0), (2, 0), (2, 1), (1, 1), (0, 1)]);
+
+        let spiral = PlacementOrder::Spiral.cells(3, 3, 0, None);
+        assert_eq!(spiral[0], (1, 1));
+        assert!(spiral.windows(2).all(|pair| {
+            let (a, b) = (pair[0], pair[1]);
+            a.0.abs_diff(b.0) + a.1.abs_diff(b.1) == 1
+        }));
+    }
+
+    #[test]
+    fn test_random_order_is_seeded() {
+        let first = PlacementOrder::Random.cells(6, 5, 42, None);
+        assert_eq!(first, PlacementOrder::Random.cells(6, 5, 42, None));
+        assert_ne!(first, PlacementOrder::Random.cells(6, 5, 43, None));
+        assert_ne!(first, PlacementOrder::Raster.cells(6, 5, 42, None));
+    }
+
+    #[test]
+    fn test_hardest_first_sorts_by_difficulty() {
+        let difficulty = vec![vec![1.0, 5.0, 1.0], vec![3.0, 1.0, 9.0]];
+        let cells = PlacementOrder::HardestFirst.cells(3, 2, 0, Some(&difficulty));
+        assert_eq!(cells, [(2, 1), (1, 0), (0, 1), (0, 0), (2, 0), (1, 1)]);
+        assert_eq!(
+            "hardest".parse::<PlacementOrder>().unwrap(),
+            PlacementOrder::HardestFirst
+        );
+        assert!("zigzag".parse::<PlacementOrder>().is_err());
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::{anyhow, Result};
+use image::RgbImage;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::image_loader;
+use crate::limits::ResourceLimits;
+use crate::manifest::parse_hex_rgb;
+use crate::Tile;
+
+/// A material region: the set of tags whose tiles may be placed inside it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub color: [u8; 3],
+    pub tags: Vec<String>,
+}
+
+impl Region {
+    /// Check whether a tile belongs to this region's material set
+    pub fn allows(&self, tile: &Tile) -> bool {
+        self.tags.iter().any(|tag| tile.has_tag(tag))
+    }
+}
+
+/// A label image aligned with the target that restricts which materials each cell may use
+#[derive(Debug, Clone)]
+pub struct RegionMask {
+    labels: RgbImage,
+    regions: Vec<Region>,
+}
+
+impl RegionMask {
+    /// Create a mask from a label image and `(color, tag)` mappings
+    ///
+    /// Mapping the same color more than once allows several tags in that region.
+    pub fn new(labels: RgbImage, mappings: &[([u8; 3], String)]) -> Self {
+        let mut regions: Vec<Region> = Vec::new();
+        for (color, tag) in mappings {
+            match regions.iter_mut().find(|r| r.color == *color) {
+                Some(region) => region.tags.push(tag.clone()),
+                None => regions.push(Region {
+                    color: *color,
+                    tags: vec![tag.clone()],
+                }),
+            }
+        }
+        Self { labels, regions }
+    }
+
+    /// Load a label image from disk, subject to the same limits as the target
+    pub fn load(
+        path: &Path,
+        mappings: &[([u8; 3], String)],
+        limits: &ResourceLimits,
+    ) -> Result<Self> {
+        let labels = image_loader::open_image(path, limits)?.to_rgb8();
+        Ok(Self::new(labels, mappings))
+    }
+
+    /// Parse a `#rrggbb=tag` mapping as given on the command line
+    pub fn parse_mapping(spec: &str) -> Result<([u8; 3], String)> {
+        let (color, tag) = spec
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Invalid region mapping {spec:?} (expected #rrggbb=tag)"))?;
+        let tag = tag.trim();
+        if tag.is_empty() {
+            return Err(anyhow!("Region mapping {spec:?} has an empty tag"));
+        }
+        Ok((parse_hex_rgb(color)?, tag.to_string()))
+    }
+
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    /// Assign each grid cell to the region covering most of its area
+    ///
+    /// Cells dominated by unmapped colors are unrestricted (`None`). The label image is
+    /// scaled to the grid, so it only needs the target's proportions, not its resolution.
+    pub fn classify_cells(&self, grid_width: usize, grid_height: usize) -> Vec<Vec<Option<usize>>> {
+        let (mask_width, mask_height) = self.labels.dimensions();
+        let color_to_region: HashMap<[u8; 3], usize> = self
+            .regions
+            .iter()
+            .enumerate()
+            .map(|(idx, region)| (region.color, idx))
+            .collect();
+
+        let mut cells = vec![vec![None; grid_width]; grid_height];
+        if mask_width == 0 || mask_height == 0 {
+            return cells;
+        }
+
+        for (y, row) in cells.iter_mut().enumerate() {
+            let y0 = mask_offset(y, mask_height, grid_height);
+            let y1 = mask_offset(y + 1, mask_height, grid_height).max(y0 + 1);
+            for (x, cell) in row.iter_mut().enumerate() {
+                let x0 = mask_offset(x, mask_width, grid_width);
+                let x1 = mask_offset(x + 1, mask_width, grid_width).max(x0 + 1);
+
+                // Vote with one extra slot for unmapped pixels
+                let mut votes = vec![0usize; self.regions.len() + 1];
+                for py in y0..y1.min(mask_height) {
+                    for px in x0..x1.min(mask_width) {
+                        let color = self.labels.get_pixel(px, py).0;
+                        let slot = color_to_region
+                            .get(&color)
+                            .copied()
+                            .unwrap_or(self.regions.len());
+                        votes[slot] += 1;
+                    }
+                }
+
+                let (winner, _) = votes
+                    .iter()
+                    .enumerate()
+                    .max_by_key(|(idx, count)| (**count, std::cmp::Reverse(*idx)))
+                    .unwrap_or((self.regions.len(), &0));
+                if winner < self.regions.len() {
+                    *cell = Some(winner);
+                }
+            }
+        }
+
+        cells
+    }
+}
+
+/// Mask pixel where grid line `index` falls, computed in u64 so large grids and masks
+/// don't overflow
+fn mask_offset(index: usize, mask_len: u32, grid_len: usize) -> u32 {
+    (index as u64 * u64::from(mask_len) / grid_len as u64) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::LimitError;
+    use image::{ImageBuffer, Rgb};
+    use palette::Lab;
+    use std::path::PathBuf;
+
+    const RED: [u8; 3] = [255, 0, 0];
+    const GREEN: [u8; 3] = [0, 255, 0];
+
+    /// 40x40 mask: red border, green center
+    fn border_mask() -> RgbImage {
+        ImageBuffer::from_fn(40, 40, |x, y| {
+            if (10..30).contains(&x) && (10..30).contains(&y) {
+                Rgb(GREEN)
+            } else {
+                Rgb(RED)
+            }
+        })
+    }
+
+    #[test]
+    fn test_parse_mapping() {
+        assert_eq!(
+            RegionMask::parse_mapping("#ff0000=sponsor").unwrap(),
+            (RED, "sponsor".to_string())
+        );
+        assert!(RegionMask::parse_mapping("#ff0000").is_err());
+        assert!(RegionMask::parse_mapping("#ff0000=").is_err());
+        assert!(RegionMask::parse_mapping("red=sponsor").is_err());
+    }
+
+    #[test]
+    fn test_repeated_color_merges_tags() {
+        let mask = RegionMask::new(
+            border_mask(),
+            &[
+                (RED, "sponsor".to_string()),
+                (GREEN, "crowd".to_string()),
+                (RED, "logo".to_string()),
+            ],
+        );
+
+        assert_eq!(mask.regions().len(), 2);
+        assert_eq!(mask.regions()[0].tags, vec!["sponsor", "logo"]);
+    }
+
+    #[test]
+    fn test_classify_cells_border_and_center() {
+        let mask = RegionMask::new(
+            border_mask(),
+            &[(RED, "sponsor".to_string()), (GREEN, "crowd".to_string())],
+        );
+
+        let cells = mask.classify_cells(4, 4);
+
+        assert_eq!(cells[0][0], Some(0));
+        assert_eq!(cells[3][2], Some(0));
+        assert_eq!(cells[1][1], Some(1));
+        assert_eq!(cells[2][2], Some(1));
+    }
+
+    #[test]
+    fn test_unmapped_colors_are_unrestricted() {
+        let mask = RegionMask::new(border_mask(), &[(GREEN, "crowd".to_string())]);
+
+        let cells = mask.classify_cells(4, 4);
+
+        assert_eq!(cells[0][0], None);
+        assert_eq!(cells[1][1], Some(0));
+    }
+
+    #[test]
+    fn test_classify_cells_grid_finer_than_mask() {
+        let labels = ImageBuffer::from_fn(2, 1, |x, _| if x == 0 { Rgb(RED) } else { Rgb(GREEN) });
+        let mask = RegionMask::new(labels, &[(RED, "a".to_string()), (GREEN, "b".to_string())]);
+
+        let cells = mask.classify_cells(4, 2);
+
+        assert_eq!(cells[0], vec![Some(0), Some(0), Some(1), Some(1)]);
+        assert_eq!(cells[1], vec![Some(0), Some(0), Some(1), Some(1)]);
+    }
+
+    #[test]
+    fn test_mask_offset_does_not_overflow() {
+        // 1M grid lines over a 20 000 px mask exceed u32 before dividing
+        assert_eq!(mask_offset(999_999, 20_000, 1_000_000), 19_999);
+        assert_eq!(mask_offset(1_000_000, 20_000, 1_000_000), 20_000);
+        assert_eq!(mask_offset(500_000, 20_000, 1_000_000), 10_000);
+    }
+
+    #[test]
+    fn test_load_respects_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mask.png");
+        ImageBuffer::from_pixel(64, 32, Rgb([255u8, 0, 0]))
+            .save(&path)
+            .unwrap();
+        let mappings = [([255, 0, 0], "red".to_string())];
+
+        let mask = RegionMask::load(&path, &mappings, &ResourceLimits::default()).unwrap();
+        assert_eq!(mask.classify_cells(2, 1), vec![vec![Some(0), Some(0)]]);
+
+        let limits = ResourceLimits {
+            max_image_dimension: 32,
+            ..Default::default()
+        };
+        let err = RegionMask::load(&path, &mappings, &limits).unwrap_err();
+        assert!(err.downcast_ref::<LimitError>().is_some());
+    }
+
+    #[test]
+    fn test_region_allows_tagged_tiles() {
+        let region = Region {
+            color: RED,
+            tags: vec!["sponsor".to_string()],
+        };
+        let mut tile = Tile::new(PathBuf::from("a.png"), Lab::new(50.0, 0.0, 0.0), 1.0);
+        assert!(!region.allows(&tile));
+
+        tile.tags.push("sponsor".to_string());
+        assert!(region.allows(&tile));
+    }
+}
@@ -0,0 +1,241 @@
+use crate::tile_grid::{TileGrid, TileId};
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// How the distance between two grid cells is measured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReuseMetric {
+    /// Largest of the horizontal and vertical offsets; diagonal neighbors are 1 apart
+    #[default]
+    Chebyshev,
+    /// Straight-line distance; diagonal neighbors are √2 apart
+    Euclidean,
+}
+
+impl ReuseMetric {
+    pub fn distance(&self, a: (usize, usize), b: (usize, usize)) -> f32 {
+        let dx = a.0.abs_diff(b.0) as f32;
+        let dy = a.1.abs_diff(b.1) as f32;
+        match self {
+            Self::Chebyshev => dx.max(dy),
+            Self::Euclidean => (dx * dx + dy * dy).sqrt(),
+        }
+    }
+}
+
+impl FromStr for ReuseMetric {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "chebyshev" => Ok(Self::Chebyshev),
+            "euclidean" => Ok(Self::Euclidean),
+            _ => bail!("Unknown reuse metric {s:?} (expected chebyshev or euclidean)"),
+        }
+    }
+}
+
+impl fmt::Display for ReuseMetric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Chebyshev => f.write_str("chebyshev"),
+            Self::Euclidean => f.write_str("euclidean"),
+        }
+    }
+}
+
+/// Minimum distance between any two placements of the same material
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ReuseDistance {
+    /// Copies must be at least this far apart; 1.0 or less only keeps them in separate cells
+    pub min_distance: f32,
+    pub metric: ReuseMetric,
+}
+
+impl ReuseDistance {
+    pub fn is_enabled(&self) -> bool {
+        self.min_distance > 1.0
+    }
+}
+
+/// Cells of one material's copies, keyed by bucket
+type Buckets = HashMap<(usize, usize), Vec<(usize, usize)>>;
+
+/// Where each material has been placed, bucketed so a placement is checked only against
+/// the copies in nearby buckets
+#[derive(Debug, Clone, Default)]
+pub struct ReuseIndex {
+    rule: ReuseDistance,
+    /// Side of a bucket in cells; no conflicting copy is more than one bucket away
+    bucket: usize,
+    placements: HashMap<TileId, Buckets>,
+}
+
+impl ReuseIndex {
+    pub fn new(rule: ReuseDistance) -> Self {
+        Self {
+            rule,
+            bucket: (rule.min_distance.ceil() as usize).max(1),
+            placements: HashMap::new(),
+        }
+    }
+
+    /// Index every placement of a grid
+    pub fn from_grid(rule: ReuseDistance, grid: &TileGrid) -> Self {
+        let mut index = Self::new(rule);
+        if rule.is_enabled() {
+            for (position, tile) in grid.placed() {
+                index.insert(tile, position.x, position.y);
+            }
+        }
+        index
+    }
+
+    pub fn rule(&self) -> ReuseDistance {
+        self.rule
+    }
+
+    pub fn insert(&mut self, tile: TileId, x: usize, y: usize) {
+        if !self.rule.is_enabled() {
+            return;
+        }
+        self.placements
+            .entry(tile)
+            .or_default()
+            .entry((x / self.bucket, y / self.bucket))
+            .or_default()
+            .push((x, y));
+    }
+
+    pub fn remove(&mut self, tile: TileId, x: usize, y: usize) {
+        let bucket = (x / self.bucket, y / self.bucket);
+        if let Some(cells) = self
+            .placements
+            .get_mut(&tile)
+            .and_then(|buckets| buckets.get_mut(&bucket))
+        {
+            cells.retain(|&cell| cell != (x, y));
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.placements.clear();
+    }
+
+    /// Whether `tile` may be placed at `(x, y)` without coming too close to its copies
+    pub fn allows(&self, tile: TileId, x: usize, y: usize) -> bool {
+        self.allows_except(tile, x, y, None)
+    }
+
+    /// Like [`Self::allows`], but ignoring the copy at `moving_from`, which is about to move
+    pub fn allows_move(
+        &self,
+        tile: TileId,
+        moving_from: (usize, usize),
+        to: (usize, usize),
+    ) -> bool {
+        self.allows_except(tile, to.0, to.1, Some(moving_from))
+    }
+
+    fn allows_except(
+        &self,
+        tile: TileId,
+        x: usize,
+        y: usize,
+        ignore: Option<(usize, usize)>,
+    ) -> bool {
+        if !self.rule.is_enabled() {
+            return true;
+        }
+        let Some(buckets) = self.placements.get(&tile) else {
+            return true;
+        };
+        let (bx, by) = (x / self.bucket, y / self.bucket);
+        for nby in by.saturating_sub(1)..=by + 1 {
+            for nbx in bx.saturating_sub(1)..=bx + 1 {
+                let Some(cells) = buckets.get(&(nbx, nby)) else {
+                    continue;
+                };
+                let too_close = cells.iter().any(|&cell| {
+                    Some(cell) != ignore
+                        && self.rule.metric.distance(cell, (x, y)) < self.rule.min_distance
+                });
+                if too_close {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(min_distance: f32, metric: ReuseMetric) -> ReuseDistance {
+        ReuseDistance {
+            min_distance,
+            metric,
+        }
+    }
+
+    #[test]
+    fn test_metrics() {
+        assert_eq!(ReuseMetric::Chebyshev.distance((1, 1), (3, 2)), 2.0);
+        assert_eq!(ReuseMetric::Euclidean.distance((0, 0), (3, 4)), 5.0);
+        assert_eq!(
+            "Euclidean".parse::<ReuseMetric>().unwrap(),
+            ReuseMetric::Euclidean
+        );
+        assert!("manhattan".parse::<ReuseMetric>().is_err());
+    }
+
+    #[test]
+    fn test_index_matches_brute_force() {
+        let tile = TileId::new(0);
+        for rule in [
+            rule(2.0, ReuseMetric::Chebyshev),
+            rule(3.0, ReuseMetric::Chebyshev),
+            rule(2.5, ReuseMetric::Euclidean),
+            rule(4.0, ReuseMetric::Euclidean),
+        ] {
+            let placed = [(0, 0), (7, 3), (4, 9), (12, 12)];
+            let mut index = ReuseIndex::new(rule);
+            for &(x, y) in &placed {
+                index.insert(tile, x, y);
+            }
+            for y in 0..15 {
+                for x in 0..15 {
+                    let expected = placed
+                        .iter()
+                        .all(|&cell| rule.metric.distance(cell, (x, y)) >= rule.min_distance);
+                    assert_eq!(index.allows(tile, x, y), expected, "{rule:?} ({x}, {y})");
+                }
+            }
+            assert!(index.allows(TileId::new(1), 1, 0));
+        }
+    }
+
+    #[test]
+    fn test_moves_and_removal() {
+        let tile = TileId::new(0);
+        let mut index = ReuseIndex::new(rule(3.0, ReuseMetric::Chebyshev));
+        index.insert(tile, 5, 5);
+        assert!(!index.allows(tile, 6, 7));
+        // The only copy may move next to where it was
+        assert!(index.allows_move(tile, (5, 5), (6, 7)));
+
+        index.insert(tile, 0, 0);
+        assert!(!index.allows_move(tile, (5, 5), (2, 2)));
+        index.remove(tile, 0, 0);
+        assert!(index.allows_move(tile, (5, 5), (2, 2)));
+
+        // Disabled rules allow anything and index nothing
+        let mut disabled = ReuseIndex::new(rule(1.0, ReuseMetric::Euclidean));
+        disabled.insert(tile, 0, 0);
+        assert!(disabled.allows(tile, 0, 0));
+    }
+}
@@ -0,0 +1,149 @@
+use rand::Rng;
+
+/// Default score gap for candidates to be sampled; scores are squared Lab distances, so
+/// behind an exact match this admits candidates up to ΔE 10 away
+pub const DEFAULT_TOLERANCE: f32 = 100.0;
+/// Default softmax temperature: each 25 points of score make a candidate e times less likely
+pub const DEFAULT_TEMPERATURE: f32 = 25.0;
+
+/// How the matcher chooses among scored candidates
+///
+/// With `top_k` of 1 the lowest score always wins, which is the deterministic default.
+/// Larger values sample among the `top_k` best candidates whose score is within
+/// `tolerance` of the best, with softmax probabilities `exp(-(score - best) / temperature)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CandidateSampling {
+    pub top_k: usize,
+    /// Maximum score gap to the best candidate for a candidate to be eligible
+    pub tolerance: f32,
+    /// Softmax temperature, in score units; lower values favor the best candidate more
+    pub temperature: f32,
+}
+
+impl Default for CandidateSampling {
+    fn default() -> Self {
+        Self {
+            top_k: 1,
+            tolerance: DEFAULT_TOLERANCE,
+            temperature: DEFAULT_TEMPERATURE,
+        }
+    }
+}
+
+impl CandidateSampling {
+    pub fn is_deterministic(&self) -> bool {
+        self.top_k <= 1
+    }
+
+    /// Pick one of the `(score, candidate)` pairs; lower scores are better
+    ///
+    /// Deterministic selection returns the first of equally scored candidates.
+    pub fn choose<T, R: Rng>(&self, mut scored: Vec<(f32, T)>, rng: &mut R) -> Option<T> {
+        if self.is_deterministic() {
+            let mut best: Option<(f32, T)> = None;
+            for (score, candidate) in scored {
+                if best
+                    .as_ref()
+                    .is_none_or(|(best_score, _)| score < *best_score)
+                {
+                    best = Some((score, candidate));
+                }
+            }
+            return best.map(|(_, candidate)| candidate);
+        }
+
+        scored.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let best = scored.first()?.0;
+        let eligible = scored
+            .iter()
+            .take(self.top_k)
+            .take_while(|(score, _)| *score - best <= self.tolerance)
+            .count();
+        scored.truncate(eligible);
+
+        let temperature = self.temperature.max(f32::EPSILON);
+        let weights: Vec<f32> = scored
+            .iter()
+            .map(|(score, _)| (-(score - best) / temperature).exp())
+            .collect();
+        let mut pick = rng.gen::<f32>() * weights.iter().sum::<f32>();
+        let index = weights
+            .iter()
+            .position(|weight| {
+                pick -= weight;
+                pick < 0.0
+            })
+            .unwrap_or(eligible - 1);
+        Some(scored.swap_remove(index).1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn counts(sampling: &CandidateSampling, scores: &[f32], draws: usize) -> Vec<usize> {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut counts = vec![0; scores.len()];
+        for _ in 0..draws {
+            let scored = scores.iter().copied().zip(0usize..).collect();
+            counts[sampling.choose(scored, &mut rng).unwrap()] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn test_deterministic_picks_first_best() {
+        let sampling = CandidateSampling::default();
+        let mut rng = StdRng::seed_from_u64(0);
+        let scored = vec![(3.0, "a"), (1.0, "b"), (1.0, "c")];
+        assert_eq!(sampling.choose(scored, &mut rng), Some("b"));
+        assert_eq!(sampling.choose(Vec::<(f32, ())>::new(), &mut rng), None);
+    }
+
+    #[test]
+    fn test_sampling_respects_top_k_and_tolerance() {
+        let scores = [0.0, 2.0, 9.0, 1.0, 3.0];
+        let top_three = CandidateSampling {
+            top_k: 3,
+            tolerance: f32::INFINITY,
+            temperature: 100.0,
+        };
+        let counts_top = counts(&top_three, &scores, 2000);
+        assert_eq!((counts_top[2], counts_top[4]), (0, 0));
+        assert!(
+            counts_top[0] > 500 && counts_top[1] > 500 && counts_top[3] > 500,
+            "{counts_top:?}"
+        );
+
+        // Room for four, but the score 3.0 is further than 2.5 from the best
+        let within_tolerance = CandidateSampling {
+            top_k: 4,
+            tolerance: 2.5,
+            ..top_three
+        };
+        let counts_tolerance = counts(&within_tolerance, &scores, 2000);
+        assert_eq!((counts_tolerance[2], counts_tolerance[4]), (0, 0));
+    }
+
+    #[test]
+    fn test_temperature_controls_preference() {
+        let cold = CandidateSampling {
+            top_k: 2,
+            tolerance: f32::INFINITY,
+            temperature: 0.01,
+        };
+        assert_eq!(counts(&cold, &[0.0, 1.0], 500), [500, 0]);
+
+        let warm = CandidateSampling {
+            temperature: 1.0,
+            ..cold
+        };
+        // Weights 1 and e^-1: the better candidate wins about 73% of draws
+        let counts = counts(&warm, &[0.0, 1.0], 4000);
+        let share = counts[0] as f32 / 4000.0;
+        assert!((share - 0.731).abs() < 0.03, "{share}");
+    }
+}
@@ -1,3 +1,4 @@
+use crate::tile_grid::TileId;
 use palette::Lab;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -83,25 +84,37 @@ impl SimilarityDatabase {
 
     /// Get similarity between two images by path
     pub fn get_similarity(&self, path1: &Path, path2: &Path) -> Option<f32> {
-        let idx1 = self.path_to_index.get(path1)?;
-        let idx2 = self.path_to_index.get(path2)?;
+        self.similarity(self.tile_id(path1)?, self.tile_id(path2)?)
+    }
 
-        if idx1 == idx2 {
+    /// Get similarity between two images by id, without any path lookups
+    pub fn similarity(&self, id1: TileId, id2: TileId) -> Option<f32> {
+        let (i, j) = (id1.index().min(id2.index()), id1.index().max(id2.index()));
+        let n = self.lab_colors.len();
+        if j >= n {
+            return None;
+        }
+        if i == j {
             return Some(0.0);
         }
 
-        let (i, j) = if idx1 < idx2 {
-            (*idx1, *idx2)
-        } else {
-            (*idx2, *idx1)
-        };
-
-        let n = self.lab_colors.len();
         let position = i * n - i * (i + 1) / 2 + j - i - 1;
 
         self.similarities.get(position).copied()
     }
 
+    /// Id of an image in the database
+    pub fn tile_id(&self, path: &Path) -> Option<TileId> {
+        self.path_to_index
+            .get(path)
+            .map(|&index| TileId::new(index))
+    }
+
+    /// Path of the image with the given id
+    pub fn tile_path(&self, id: TileId) -> Option<&Path> {
+        self.index_to_path.get(&id.index()).map(PathBuf::as_path)
+    }
+
     /// Get the Lab color for a given path
     pub fn get_lab_color(&self, path: &Path) -> Option<Lab> {
         let idx = self.path_to_index.get(path)?;
@@ -203,6 +216,26 @@ mod tests {
         assert_eq!(sim_same, Some(0.0));
     }
 
+    #[test]
+    fn test_similarity_by_id() {
+        let mut db = SimilarityDatabase::new();
+        db.add_tile(PathBuf::from("tile1.png"), Lab::new(50.0, 0.0, 0.0));
+        db.add_tile(PathBuf::from("tile2.png"), Lab::new(60.0, 10.0, 10.0));
+        db.add_tile(PathBuf::from("tile3.png"), Lab::new(40.0, -10.0, -10.0));
+        db.build_similarities();
+
+        let id1 = db.tile_id(Path::new("tile1.png")).unwrap();
+        let id3 = db.tile_id(Path::new("tile3.png")).unwrap();
+        assert_eq!(db.tile_path(id3), Some(Path::new("tile3.png")));
+        assert_eq!(
+            db.similarity(id3, id1),
+            db.get_similarity(Path::new("tile1.png"), Path::new("tile3.png"))
+        );
+        assert_eq!(db.similarity(id1, id1), Some(0.0));
+        assert!(db.tile_id(Path::new("missing.png")).is_none());
+        assert_eq!(db.similarity(id1, TileId::new(3)), None);
+    }
+
     #[test]
     fn test_lab_distance_calculation() {
         let lab1 = Lab::new(50.0, 0.0, 0.0);
@@ -0,0 +1,330 @@
+use anyhow::{bail, Result};
+use image::{imageops::FilterType, DynamicImage};
+use palette::{FromColor, Lab, Srgb};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// How to choose `max_materials` tiles when more candidates are available
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SelectionStrategy {
+    /// Keep candidates in listing order
+    #[default]
+    First,
+    /// Seeded uniform random sample
+    Random,
+    /// Farthest-point sampling in Lab for even color coverage
+    FarthestPoint,
+    /// Prefer materials close to the colors that occur in the target
+    TargetAware,
+}
+
+impl FromStr for SelectionStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "first" => Ok(Self::First),
+            "random" => Ok(Self::Random),
+            "farthest" | "farthest-point" => Ok(Self::FarthestPoint),
+            "target" | "target-aware" => Ok(Self::TargetAware),
+            _ => bail!(
+                "Unknown selection strategy {s:?} (expected first, random, farthest or target)"
+            ),
+        }
+    }
+}
+
+impl fmt::Display for SelectionStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::First => "first",
+            Self::Random => "random",
+            Self::FarthestPoint => "farthest",
+            Self::TargetAware => "target",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Choose up to `count` of `colors`, returning their indices in ascending order
+///
+/// `target_colors` is only used by [`SelectionStrategy::TargetAware`], which falls back to
+/// farthest-point sampling when it is empty.
+pub fn select_subset(
+    colors: &[Lab],
+    count: usize,
+    strategy: SelectionStrategy,
+    seed: u64,
+    target_colors: &[Lab],
+) -> Vec<usize> {
+    if count >= colors.len() {
+        return (0..colors.len()).collect();
+    }
+
+    let mut selected = match strategy {
+        SelectionStrategy::First => (0..count).collect(),
+        SelectionStrategy::Random => random_indices(colors.len(), count, seed),
+        SelectionStrategy::FarthestPoint => farthest_point(colors, count, Vec::new()),
+        SelectionStrategy::TargetAware if target_colors.is_empty() => {
+            farthest_point(colors, count, Vec::new())
+        }
+        SelectionStrategy::TargetAware => target_aware(colors, count, target_colors),
+    };
+    selected.sort_unstable();
+    selected
+}
+
+/// Seeded sample of `count` distinct indices from `0..len`, in ascending order
+pub fn random_indices(len: usize, count: usize, seed: u64) -> Vec<usize> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut indices = rand::seq::index::sample(&mut rng, len, count.min(len)).into_vec();
+    indices.sort_unstable();
+    indices
+}
+
+/// Sample the target's colors on a `grid_w` x `grid_h` lattice (one color per cell)
+pub fn sample_target_colors(target: &DynamicImage, grid_w: u32, grid_h: u32) -> Vec<Lab> {
+    target
+        .resize_exact(grid_w.max(1), grid_h.max(1), FilterType::Triangle)
+        .to_rgb8()
+        .pixels()
+        .map(|p| Lab::from_color(Srgb::new(p[0], p[1], p[2]).into_format::<f32>()))
+        .collect()
+}
+
+fn distance_squared(a: &Lab, b: &Lab) -> f32 {
+    (a.l - b.l).powi(2) + (a.a - b.a).powi(2) + (a.b - b.b).powi(2)
+}
+
+/// Greedily add the color farthest from everything chosen so far
+///
+/// Starts from `seeds` if given, otherwise from the color farthest from the mean.
+fn farthest_point(colors: &[Lab], count: usize, seeds: Vec<usize>) -> Vec<usize> {
+    let mut selected = seeds;
+    let mut min_distance = vec![f32::INFINITY; colors.len()];
+
+    if selected.is_empty() {
+        let n = colors.len() as f32;
+        let mean = colors.iter().fold(Lab::new(0.0, 0.0, 0.0), |acc, c| {
+            Lab::new(acc.l + c.l / n, acc.a + c.a / n, acc.b + c.b / n)
+        });
+        let first = (0..colors.len())
+            .max_by(|&i, &j| {
+                distance_squared(&colors[i], &mean).total_cmp(&distance_squared(&colors[j], &mean))
+            })
+            .unwrap_or(0);
+        selected.push(first);
+    }
+
+    let mut chosen = vec![false; colors.len()];
+    for &idx in &selected {
+        chosen[idx] = true;
+        update_min_distance(colors, idx, &mut min_distance);
+    }
+
+    while selected.len() < count {
+        let Some(next) = (0..colors.len())
+            .filter(|&idx| !chosen[idx])
+            .max_by(|&i, &j| min_distance[i].total_cmp(&min_distance[j]))
+        else {
+            break;
+        };
+        chosen[next] = true;
+        selected.push(next);
+        update_min_distance(colors, next, &mut min_distance);
+    }
+
+    selected
+}
+
+fn update_min_distance(colors: &[Lab], chosen: usize, min_distance: &mut [f32]) {
+    for (idx, color) in colors.iter().enumerate() {
+        min_distance[idx] = min_distance[idx].min(distance_squared(color, &colors[chosen]));
+    }
+}
+
+/// Cover the target's color histogram, giving common target colors more materials
+///
+/// Target colors are binned in Lab; each bin gets a share of `count` proportional to how
+/// often it occurs and takes the nearest unused materials to its mean color. Any slots a
+/// bin cannot fill are topped up by farthest-point sampling.
+fn target_aware(colors: &[Lab], count: usize, target_colors: &[Lab]) -> Vec<usize> {
+    const BIN_L: f32 = 10.0;
+    const BIN_AB: f32 = 16.0;
+
+    let mut bins: HashMap<(i32, i32, i32), (usize, Lab)> = HashMap::new();
+    for color in target_colors {
+        let key = (
+            (color.l / BIN_L).floor() as i32,
+            (color.a / BIN_AB).floor() as i32,
+            (color.b / BIN_AB).floor() as i32,
+        );
+        let (n, sum) = bins.entry(key).or_insert((0, Lab::new(0.0, 0.0, 0.0)));
+        *n += 1;
+        *sum = Lab::new(sum.l + color.l, sum.a + color.a, sum.b + color.b);
+    }
+
+    // Most frequent bins first; ties broken by key so the result is deterministic
+    let mut bins: Vec<_> = bins
+        .into_iter()
+        .map(|(key, (n, sum))| {
+            let mean = Lab::new(sum.l / n as f32, sum.a / n as f32, sum.b / n as f32);
+            (key, n, mean)
+        })
+        .collect();
+    bins.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    // Largest-remainder apportionment of `count` across bins
+    let total = target_colors.len() as f64;
+    let exact: Vec<f64> = bins
+        .iter()
+        .map(|(_, n, _)| *n as f64 * count as f64 / total)
+        .collect();
+    let mut quotas: Vec<usize> = exact.iter().map(|q| q.floor() as usize).collect();
+    let mut remaining = count.saturating_sub(quotas.iter().sum::<usize>());
+    let mut by_remainder: Vec<usize> = (0..bins.len()).collect();
+    by_remainder
+        .sort_by(|&i, &j| (exact[j] - exact[j].floor()).total_cmp(&(exact[i] - exact[i].floor())));
+    for idx in by_remainder {
+        if remaining == 0 {
+            break;
+        }
+        quotas[idx] += 1;
+        remaining -= 1;
+    }
+
+    let mut used = vec![false; colors.len()];
+    let mut selected = Vec::with_capacity(count);
+    for ((_, _, mean), quota) in bins.iter().zip(quotas) {
+        let mut nearest: Vec<usize> = (0..colors.len()).filter(|&idx| !used[idx]).collect();
+        nearest.sort_by(|&i, &j| {
+            distance_squared(&colors[i], mean).total_cmp(&distance_squared(&colors[j], mean))
+        });
+        for idx in nearest.into_iter().take(quota) {
+            used[idx] = true;
+            selected.push(idx);
+        }
+    }
+
+    if selected.len() < count {
+        selected = farthest_point(colors, count, selected);
+    }
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray_ramp(n: usize) -> Vec<Lab> {
+        (0..n)
+            .map(|i| Lab::new(i as f32 * 100.0 / (n - 1) as f32, 0.0, 0.0))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_strategy() {
+        assert_eq!(
+            "first".parse::<SelectionStrategy>().unwrap(),
+            SelectionStrategy::First
+        );
+        assert_eq!(
+            "Farthest".parse::<SelectionStrategy>().unwrap(),
+            SelectionStrategy::FarthestPoint
+        );
+        assert_eq!(
+            "target-aware".parse::<SelectionStrategy>().unwrap(),
+            SelectionStrategy::TargetAware
+        );
+        assert!("best".parse::<SelectionStrategy>().is_err());
+        assert_eq!(SelectionStrategy::Random.to_string(), "random");
+    }
+
+    #[test]
+    fn test_no_selection_needed() {
+        let colors = gray_ramp(3);
+        assert_eq!(
+            select_subset(&colors, 5, SelectionStrategy::Random, 1, &[]),
+            vec![0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn test_first_keeps_listing_order() {
+        let colors = gray_ramp(10);
+        assert_eq!(
+            select_subset(&colors, 3, SelectionStrategy::First, 0, &[]),
+            vec![0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn test_random_is_reproducible_per_seed() {
+        let colors = gray_ramp(100);
+
+        let a = select_subset(&colors, 10, SelectionStrategy::Random, 42, &[]);
+        let b = select_subset(&colors, 10, SelectionStrategy::Random, 42, &[]);
+        let c = select_subset(&colors, 10, SelectionStrategy::Random, 7, &[]);
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a.len(), 10);
+        assert!(a.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_farthest_point_covers_extremes() {
+        // Many near-duplicates of mid gray plus a black and a white
+        let mut colors = vec![Lab::new(50.0, 0.0, 0.0); 20];
+        colors.push(Lab::new(0.0, 0.0, 0.0));
+        colors.push(Lab::new(100.0, 0.0, 0.0));
+
+        let selected = select_subset(&colors, 3, SelectionStrategy::FarthestPoint, 0, &[]);
+
+        assert_eq!(selected.len(), 3);
+        assert!(selected.contains(&20));
+        assert!(selected.contains(&21));
+    }
+
+    #[test]
+    fn test_target_aware_follows_target_histogram() {
+        let colors = gray_ramp(101);
+        // Mostly dark target with a little white
+        let mut target = vec![Lab::new(5.0, 0.0, 0.0); 9];
+        target.push(Lab::new(95.0, 0.0, 0.0));
+
+        let selected = select_subset(&colors, 10, SelectionStrategy::TargetAware, 0, &target);
+
+        assert_eq!(selected.len(), 10);
+        let dark = selected.iter().filter(|&&i| i < 20).count();
+        let light = selected.iter().filter(|&&i| i > 80).count();
+        assert_eq!(dark, 9);
+        assert_eq!(light, 1);
+    }
+
+    #[test]
+    fn test_target_aware_without_target_uses_farthest_point() {
+        let colors = gray_ramp(50);
+        assert_eq!(
+            select_subset(&colors, 5, SelectionStrategy::TargetAware, 0, &[]),
+            select_subset(&colors, 5, SelectionStrategy::FarthestPoint, 0, &[])
+        );
+    }
+
+    #[test]
+    fn test_sample_target_colors() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+            40,
+            20,
+            image::Rgb([255, 255, 255]),
+        ));
+
+        let samples = sample_target_colors(&img, 4, 2);
+
+        assert_eq!(samples.len(), 8);
+        assert!(samples.iter().all(|c| (c.l - 100.0).abs() < 0.5));
+    }
+}
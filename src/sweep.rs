@@ -0,0 +1,453 @@
+use anyhow::{bail, Context, Result};
+use image::imageops::{self, FilterType};
+use image::{Rgb, RgbImage};
+use palette::Lab;
+use std::collections::HashMap;
+use std::fmt::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::placement::PlacementOrder;
+use crate::tile_grid::{TileGrid, TileId};
+
+/// Width of each variant's thumbnail on the contact sheet
+pub const THUMBNAIL_WIDTH: u32 = 400;
+/// Space between thumbnails and around the sheet's border
+const SHEET_GAP: u32 = 8;
+const SHEET_BACKGROUND: Rgb<u8> = Rgb([32, 32, 32]);
+
+/// One generation setting a variant overrides
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SweepSetting {
+    Seed(u64),
+    TopK(usize),
+    TopKTemperature(f32),
+    DitherStrength(f32),
+    AdjacencyPenaltyWeight(f32),
+    ColorAdjustmentStrength(f32),
+    PlacementOrder(PlacementOrder),
+    UsagePenaltyWeight(f32),
+}
+
+impl SweepSetting {
+    /// Parse a value for the option called `name` (its CLI name without dashes)
+    fn parse(name: &str, value: &str) -> Result<Self> {
+        let number = || -> Result<f32> {
+            value
+                .parse()
+                .with_context(|| format!("Invalid {name} value {value:?}"))
+        };
+        Ok(match name {
+            "seed" => Self::Seed(
+                value
+                    .parse()
+                    .with_context(|| format!("Invalid seed {value:?}"))?,
+            ),
+            "top-k" => Self::TopK(
+                value
+                    .parse()
+                    .with_context(|| format!("Invalid top-k {value:?}"))?,
+            ),
+            "top-k-temperature" => Self::TopKTemperature(number()?),
+            "dither-strength" => Self::DitherStrength(number()?),
+            "adjacency-penalty-weight" => Self::AdjacencyPenaltyWeight(number()?),
+            "color-adjustment-strength" => Self::ColorAdjustmentStrength(number()?),
+            "placement-order" => Self::PlacementOrder(value.parse()?),
+            "usage-penalty-weight" => Self::UsagePenaltyWeight(number()?),
+            _ => bail!(
+                "Cannot sweep {name:?} (expected seed, top-k, top-k-temperature, dither-strength, adjacency-penalty-weight, color-adjustment-strength, placement-order or usage-penalty-weight)"
+            ),
+        })
+    }
+}
+
+impl fmt::Display for SweepSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Seed(seed) => write!(f, "seed={seed}"),
+            Self::TopK(k) => write!(f, "top-k={k}"),
+            Self::TopKTemperature(t) => write!(f, "top-k-temperature={t}"),
+            Self::DitherStrength(s) => write!(f, "dither-strength={s}"),
+            Self::AdjacencyPenaltyWeight(w) => write!(f, "adjacency-penalty-weight={w}"),
+            Self::ColorAdjustmentStrength(s) => write!(f, "color-adjustment-strength={s}"),
+            Self::PlacementOrder(order) => write!(f, "placement-order={order}"),
+            Self::UsagePenaltyWeight(w) => write!(f, "usage-penalty-weight={w}"),
+        }
+    }
+}
+
+/// The variants rendered in one run: one setting each, applied on top of the CLI options
+///
+/// Written as `name=value,value,...`, e.g. `top-k=1,3,5` or `placement-order=raster,spiral`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sweep {
+    pub settings: Vec<SweepSetting>,
+}
+
+impl Sweep {
+    /// `count` variants that differ only in their seed, starting at `first`
+    pub fn seeds(first: u64, count: usize) -> Self {
+        Self {
+            settings: (0..count as u64)
+                .map(|i| SweepSetting::Seed(first.wrapping_add(i)))
+                .collect(),
+        }
+    }
+}
+
+impl FromStr for Sweep {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some((name, values)) = s.split_once('=') else {
+            bail!("Sweep {s:?} must look like name=value,value,...");
+        };
+        let name = name.trim().trim_start_matches("--").to_lowercase();
+        let settings = values
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(|value| SweepSetting::parse(&name, value))
+            .collect::<Result<Vec<_>>>()?;
+        if settings.is_empty() {
+            bail!("Sweep {s:?} lists no values");
+        }
+        Ok(Self { settings })
+    }
+}
+
+impl fmt::Display for Sweep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let labels: Vec<String> = self.settings.iter().map(ToString::to_string).collect();
+        f.write_str(&labels.join(", "))
+    }
+}
+
+/// Quality measures of one finished mosaic
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MosaicMetrics {
+    /// Mean CIE76 ΔE between each cell's color and the color of its tile
+    pub mean_delta_e: f32,
+    pub max_delta_e: f32,
+    /// Adjacency cost of the final placement at unit weight; lower means less repetition
+    pub adjacency_cost: f32,
+    pub distinct_tiles: usize,
+    /// Most cells any single material fills
+    pub max_reuse: usize,
+    /// Cells filled past their material's usage cap because no material within its cap fit
+    pub cap_overflows: usize,
+}
+
+impl MosaicMetrics {
+    /// Measure a placement from each cell's target color and the color of the tile placed
+    /// there; empty cells are skipped
+    ///
+    /// Cap overflows happen during placement and aren't visible here, so they are left at 0.
+    pub fn measure(
+        cell_labs: &[Vec<Lab>],
+        tile_labs: &[Vec<Option<Lab>>],
+        placed: &TileGrid,
+        adjacency_cost: f32,
+    ) -> Self {
+        let deltas: Vec<f32> = cell_labs
+            .iter()
+            .flatten()
+            .zip(tile_labs.iter().flatten())
+            .filter_map(|(cell, tile)| {
+                let tile = tile.as_ref()?;
+                Some(
+                    ((cell.l - tile.l).powi(2)
+                        + (cell.a - tile.a).powi(2)
+                        + (cell.b - tile.b).powi(2))
+                    .sqrt(),
+                )
+            })
+            .collect();
+
+        let mut uses: HashMap<TileId, usize> = HashMap::new();
+        for (_, tile) in placed.placed() {
+            *uses.entry(tile).or_default() += 1;
+        }
+
+        Self {
+            mean_delta_e: deltas.iter().sum::<f32>() / deltas.len().max(1) as f32,
+            max_delta_e: deltas.iter().copied().fold(0.0, f32::max),
+            adjacency_cost,
+            distinct_tiles: uses.len(),
+            max_reuse: uses.values().copied().max().unwrap_or(0),
+            cap_overflows: 0,
+        }
+    }
+}
+
+/// What one rendered variant produced
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantResult {
+    pub label: String,
+    pub output: PathBuf,
+    pub metrics: MosaicMetrics,
+    pub seconds: f32,
+}
+
+/// Where variant `index` (counting from 1) of `output` is written: `mosaic.png` becomes
+/// `mosaic-1.png`, `mosaic-2.png`, ...
+pub fn variant_path(output: &Path, index: usize) -> PathBuf {
+    sibling_path(output, &index.to_string(), None)
+}
+
+/// Path of the contact sheet for the variants of `output`
+pub fn contact_sheet_path(output: &Path) -> PathBuf {
+    sibling_path(output, "contact", None)
+}
+
+/// Path of the metrics table for the variants of `output`
+pub fn metrics_path(output: &Path) -> PathBuf {
+    sibling_path(output, "variants", Some("csv"))
+}
+
+fn sibling_path(output: &Path, suffix: &str, extension: Option<&str>) -> PathBuf {
+    let stem = output
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "mosaic".to_string());
+    let extension = extension
+        .map(str::to_string)
+        .or_else(|| output.extension().map(|e| e.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "png".to_string());
+    output.with_file_name(format!("{stem}-{suffix}.{extension}"))
+}
+
+/// Scale a rendered variant down for the contact sheet
+pub fn thumbnail(img: &RgbImage) -> RgbImage {
+    let (width, height) = img.dimensions();
+    if width <= THUMBNAIL_WIDTH {
+        return img.clone();
+    }
+    let thumb_height = ((height as u64 * THUMBNAIL_WIDTH as u64) / width as u64).max(1) as u32;
+    imageops::resize(img, THUMBNAIL_WIDTH, thumb_height, FilterType::Triangle)
+}
+
+/// Lay thumbnails out in a near-square grid, row by row in variant order
+///
+/// Cells are sized to the largest thumbnail; smaller ones sit in their cell's top-left.
+pub fn contact_sheet(thumbnails: &[RgbImage]) -> RgbImage {
+    let count = thumbnails.len().max(1) as u32;
+    let columns = (count as f32).sqrt().ceil() as u32;
+    let rows = count.div_ceil(columns);
+    let cell_width = thumbnails.iter().map(RgbImage::width).max().unwrap_or(0);
+    let cell_height = thumbnails.iter().map(RgbImage::height).max().unwrap_or(0);
+
+    let mut sheet = RgbImage::from_pixel(
+        columns * (cell_width + SHEET_GAP) + SHEET_GAP,
+        rows * (cell_height + SHEET_GAP) + SHEET_GAP,
+        SHEET_BACKGROUND,
+    );
+    for (i, thumb) in thumbnails.iter().enumerate() {
+        let (column, row) = (i as u32 % columns, i as u32 / columns);
+        imageops::replace(
+            &mut sheet,
+            thumb,
+            (SHEET_GAP + column * (cell_width + SHEET_GAP)) as i64,
+            (SHEET_GAP + row * (cell_height + SHEET_GAP)) as i64,
+        );
+    }
+    sheet
+}
+
+/// Render the variants' metrics as a table for the terminal, numbered like the contact sheet
+pub fn summary_table(results: &[VariantResult]) -> String {
+    let label_width = results
+        .iter()
+        .map(|r| r.label.len())
+        .chain(["Variant".len()])
+        .max()
+        .unwrap_or(0);
+    let mut table = String::new();
+    let _ = write!(
+        table,
+        "  {:>3}  {:<label_width$} {:>8} {:>8} {:>10} {:>8} {:>9} {:>8} {:>8}",
+        "#",
+        "Variant",
+        "Mean ΔE",
+        "Max ΔE",
+        "Adjacency",
+        "Distinct",
+        "Max reuse",
+        "Over cap",
+        "Seconds"
+    );
+    for (i, result) in results.iter().enumerate() {
+        let m = &result.metrics;
+        let _ = write!(
+            table,
+            "\n  {:>3}  {:<label_width$} {:>8.2} {:>8.2} {:>10.2} {:>8} {:>9} {:>8} {:>8.1}",
+            i + 1,
+            result.label,
+            m.mean_delta_e,
+            m.max_delta_e,
+            m.adjacency_cost,
+            m.distinct_tiles,
+            m.max_reuse,
+            m.cap_overflows,
+            result.seconds
+        );
+    }
+    table
+}
+
+/// Save the variants' metrics as CSV, one row per variant
+pub fn save_metrics(results: &[VariantResult], path: &Path) -> Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record([
+        "label",
+        "output",
+        "mean_delta_e",
+        "max_delta_e",
+        "adjacency_cost",
+        "distinct_tiles",
+        "max_reuse",
+        "cap_overflows",
+        "seconds",
+    ])?;
+    for result in results {
+        let m = &result.metrics;
+        writer.write_record([
+            result.label.clone(),
+            result.output.display().to_string(),
+            m.mean_delta_e.to_string(),
+            m.max_delta_e.to_string(),
+            m.adjacency_cost.to_string(),
+            m.distinct_tiles.to_string(),
+            m.max_reuse.to_string(),
+            m.cap_overflows.to_string(),
+            result.seconds.to_string(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_sweep() {
+        let sweep: Sweep = "top-k=1, 3,5".parse().unwrap();
+        assert_eq!(
+            sweep.settings,
+            [
+                SweepSetting::TopK(1),
+                SweepSetting::TopK(3),
+                SweepSetting::TopK(5)
+            ]
+        );
+        assert_eq!(sweep.to_string(), "top-k=1, top-k=3, top-k=5");
+
+        let orders: Sweep = "--placement-order=raster,spiral".parse().unwrap();
+        assert_eq!(
+            orders.settings[1],
+            SweepSetting::PlacementOrder(PlacementOrder::Spiral)
+        );
+        assert_eq!(
+            Sweep::seeds(7, 3).settings,
+            [
+                SweepSetting::Seed(7),
+                SweepSetting::Seed(8),
+                SweepSetting::Seed(9)
+            ]
+        );
+
+        assert!("top-k".parse::<Sweep>().is_err());
+        assert!("top-k=".parse::<Sweep>().is_err());
+        assert!("top-k=two".parse::<Sweep>().is_err());
+        assert_eq!(
+            "usage-penalty-weight=10".parse::<Sweep>().unwrap().settings,
+            [SweepSetting::UsagePenaltyWeight(10.0)]
+        );
+        assert!("grid-w=10,20".parse::<Sweep>().is_err());
+    }
+
+    #[test]
+    fn test_metrics_measure_placement() {
+        let cell_labs = vec![vec![Lab::new(50.0, 0.0, 0.0), Lab::new(50.0, 0.0, 0.0)]];
+        let tile_labs = vec![vec![Some(Lab::new(53.0, 4.0, 0.0)), None]];
+        let (a, b) = (TileId::new(0), TileId::new(1));
+        let placed = TileGrid::from_rows(vec![vec![Some(a), None], vec![Some(a), Some(b)]]);
+
+        let metrics = MosaicMetrics::measure(&cell_labs, &tile_labs, &placed, 1.5);
+        assert_eq!(metrics.mean_delta_e, 5.0);
+        assert_eq!(metrics.max_delta_e, 5.0);
+        assert_eq!(metrics.adjacency_cost, 1.5);
+        assert_eq!((metrics.distinct_tiles, metrics.max_reuse), (2, 2));
+    }
+
+    #[test]
+    fn test_output_paths() {
+        let output = Path::new("out/mosaic.jpg");
+        assert_eq!(variant_path(output, 2), Path::new("out/mosaic-2.jpg"));
+        assert_eq!(
+            contact_sheet_path(output),
+            Path::new("out/mosaic-contact.jpg")
+        );
+        assert_eq!(metrics_path(output), Path::new("out/mosaic-variants.csv"));
+    }
+
+    #[test]
+    fn test_contact_sheet_layout() {
+        let wide = RgbImage::from_pixel(800, 400, Rgb([255, 0, 0]));
+        let thumb = thumbnail(&wide);
+        assert_eq!(thumb.dimensions(), (THUMBNAIL_WIDTH, THUMBNAIL_WIDTH / 2));
+
+        let small = RgbImage::from_pixel(10, 6, Rgb([0, 0, 255]));
+        assert_eq!(thumbnail(&small), small);
+
+        // Three thumbnails fill a 2x2 grid, leaving the last cell empty
+        let sheet = contact_sheet(&[small.clone(), small.clone(), small]);
+        assert_eq!(
+            sheet.dimensions(),
+            (2 * 10 + 3 * SHEET_GAP, 2 * 6 + 3 * SHEET_GAP)
+        );
+        let second_row = SHEET_GAP + 6 + SHEET_GAP;
+        assert_eq!(*sheet.get_pixel(SHEET_GAP, second_row), Rgb([0, 0, 255]));
+        assert_eq!(
+            *sheet.get_pixel(2 * SHEET_GAP + 10, second_row),
+            SHEET_BACKGROUND
+        );
+    }
+
+    #[test]
+    fn test_metrics_table_and_csv() {
+        let results = vec![VariantResult {
+            label: "top-k=3".to_string(),
+            output: PathBuf::from("mosaic-1.png"),
+            metrics: MosaicMetrics {
+                mean_delta_e: 4.25,
+                max_delta_e: 9.0,
+                adjacency_cost: 2.0,
+                distinct_tiles: 12,
+                max_reuse: 3,
+                cap_overflows: 1,
+            },
+            seconds: 1.5,
+        }];
+        let table = summary_table(&results);
+        assert!(table.contains("top-k=3"));
+        assert!(table.contains("4.25"));
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("variants.csv");
+        save_metrics(&results, &path).unwrap();
+        let csv = std::fs::read_to_string(path).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("label,output,mean_delta_e,max_delta_e,adjacency_cost,distinct_tiles,max_reuse,cap_overflows,seconds")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("top-k=3,mosaic-1.png,4.25,9,2,12,3,1,1.5")
+        );
+    }
+}
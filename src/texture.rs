@@ -0,0 +1,147 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage};
+use std::f32::consts::PI;
+
+/// Side of the square grid images are resampled to before measuring texture
+///
+/// Materials are analyzed at proxy resolution while target cells can be a few pixels
+/// wide; measuring both at one scale keeps their descriptors comparable.
+const TEXTURE_SIZE: u32 = 32;
+/// Sobel magnitude (on 0.0-1.0 luma) above which a pixel counts as an edge
+const EDGE_THRESHOLD: f32 = 0.25;
+/// Number of gradient orientation bins covering 0-180°
+pub const ORIENTATION_BINS: usize = 8;
+
+/// How busy an image is: edge density, contrast and the direction of its edges
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureDescriptor {
+    /// Fraction of pixels on an edge (0.0-1.0)
+    pub edge_density: f32,
+    /// Standard deviation of luma, scaled so a black and white split is 1.0
+    pub contrast: f32,
+    /// Gradient magnitude per orientation bin, normalized to sum to 1.0 (all zero when flat)
+    pub orientation: [f32; ORIENTATION_BINS],
+}
+
+impl TextureDescriptor {
+    /// Measure the texture of an image
+    pub fn compute(img: &DynamicImage) -> Self {
+        let luma = img
+            .resize_exact(TEXTURE_SIZE, TEXTURE_SIZE, FilterType::Triangle)
+            .to_luma8();
+
+        let count = (TEXTURE_SIZE * TEXTURE_SIZE) as f32;
+        let mean = luma.pixels().map(|p| p[0] as f32 / 255.0).sum::<f32>() / count;
+        let variance = luma
+            .pixels()
+            .map(|p| (p[0] as f32 / 255.0 - mean).powi(2))
+            .sum::<f32>()
+            / count;
+
+        let mut edges = 0usize;
+        let mut orientation = [0.0f32; ORIENTATION_BINS];
+        for y in 1..TEXTURE_SIZE - 1 {
+            for x in 1..TEXTURE_SIZE - 1 {
+                let (gx, gy) = sobel(&luma, x, y);
+                let magnitude = (gx * gx + gy * gy).sqrt();
+                if magnitude > EDGE_THRESHOLD {
+                    edges += 1;
+                }
+                // Opposite gradients lie on the same edge, so fold angles into 0-180°
+                let angle = gy.atan2(gx).rem_euclid(PI);
+                let bin =
+                    ((angle / PI * ORIENTATION_BINS as f32) as usize).min(ORIENTATION_BINS - 1);
+                orientation[bin] += magnitude;
+            }
+        }
+
+        let total: f32 = orientation.iter().sum();
+        if total > 0.0 {
+            orientation.iter_mut().for_each(|bin| *bin /= total);
+        }
+
+        let interior = ((TEXTURE_SIZE - 2) * (TEXTURE_SIZE - 2)) as f32;
+        Self {
+            edge_density: edges as f32 / interior,
+            contrast: (variance.sqrt() * 2.0).min(1.0),
+            orientation,
+        }
+    }
+
+    /// Dissimilarity of two textures, from 0.0 (identical) to 1.0
+    ///
+    /// Edge direction only counts as far as both images have edges, so two flat images
+    /// match regardless of the noise in their orientation histograms.
+    pub fn distance(&self, other: &Self) -> f32 {
+        let orientation_difference = self
+            .orientation
+            .iter()
+            .zip(&other.orientation)
+            .map(|(a, b)| (a - b).abs())
+            .sum::<f32>()
+            / 2.0;
+        let shared_edges = self.edge_density.min(other.edge_density);
+
+        ((self.edge_density - other.edge_density).abs()
+            + (self.contrast - other.contrast).abs()
+            + orientation_difference * shared_edges)
+            / 3.0
+    }
+}
+
+/// Sobel gradient of 0.0-1.0 luma at an interior pixel
+pub(crate) fn sobel(luma: &GrayImage, x: u32, y: u32) -> (f32, f32) {
+    let value = |x: u32, y: u32| luma.get_pixel(x, y)[0] as f32 / 255.0;
+    let gx = (value(x + 1, y - 1) + 2.0 * value(x + 1, y) + value(x + 1, y + 1))
+        - (value(x - 1, y - 1) + 2.0 * value(x - 1, y) + value(x - 1, y + 1));
+    let gy = (value(x - 1, y + 1) + 2.0 * value(x, y + 1) + value(x + 1, y + 1))
+        - (value(x - 1, y - 1) + 2.0 * value(x, y - 1) + value(x + 1, y - 1));
+    (gx, gy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn stripes(vertical: bool) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(64, 64, |x, y| {
+            let coordinate = if vertical { x } else { y };
+            if (coordinate / 8) % 2 == 0 {
+                Rgb([255, 255, 255])
+            } else {
+                Rgb([0, 0, 0])
+            }
+        }))
+    }
+
+    #[test]
+    fn test_flat_and_busy_images() {
+        let flat = TextureDescriptor::compute(&DynamicImage::ImageRgb8(RgbImage::from_pixel(
+            40,
+            40,
+            Rgb([120, 60, 200]),
+        )));
+        assert_eq!(flat.edge_density, 0.0);
+        assert!(flat.contrast < 1e-3);
+        assert_eq!(flat.orientation, [0.0; ORIENTATION_BINS]);
+
+        let busy = TextureDescriptor::compute(&stripes(true));
+        assert!(busy.edge_density > 0.4, "{}", busy.edge_density);
+        assert!(busy.contrast > 0.8, "{}", busy.contrast);
+        assert!(flat.distance(&busy) > 0.4);
+        assert!(flat.distance(&flat) < 1e-6);
+    }
+
+    #[test]
+    fn test_orientation_separates_stripe_directions() {
+        let vertical = TextureDescriptor::compute(&stripes(true));
+        let horizontal = TextureDescriptor::compute(&stripes(false));
+        let rotated = TextureDescriptor::compute(&stripes(true).rotate90());
+
+        // Vertical stripes change along x, so all gradient falls in the first bin
+        assert!(vertical.orientation[0] > 0.95);
+        assert!(vertical.distance(&horizontal) > 0.1);
+        assert!(rotated.distance(&horizontal) < 1e-4);
+    }
+}
@@ -0,0 +1,157 @@
+use crate::adjacency::GridPosition;
+use std::num::NonZeroU32;
+
+/// Compact handle of a material, issued by the [`SimilarityDatabase`]
+///
+/// Grids store these instead of paths, so placing or swapping a tile copies four bytes and
+/// similarity lookups index the matrix directly. Paths are resolved only when rendering.
+///
+/// [`SimilarityDatabase`]: crate::similarity::SimilarityDatabase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TileId(NonZeroU32);
+
+impl TileId {
+    /// Id of the material at `index` in the similarity database
+    pub fn new(index: usize) -> Self {
+        // Stored off by one so `Option<TileId>` stays four bytes
+        let raw = u32::try_from(index + 1)
+            .ok()
+            .and_then(NonZeroU32::new)
+            .expect("tile index exceeds u32");
+        Self(raw)
+    }
+
+    pub fn index(self) -> usize {
+        self.0.get() as usize - 1
+    }
+}
+
+/// Material placed in each cell of a mosaic, stored row by row
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TileGrid {
+    width: usize,
+    height: usize,
+    cells: Vec<Option<TileId>>,
+}
+
+impl TileGrid {
+    /// An empty grid of `width` x `height` cells
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![None; width * height],
+        }
+    }
+
+    /// A grid holding `rows`, which must all have the same length
+    pub fn from_rows(rows: Vec<Vec<Option<TileId>>>) -> Self {
+        let width = rows.first().map_or(0, Vec::len);
+        assert!(
+            rows.iter().all(|row| row.len() == width),
+            "grid rows differ in length"
+        );
+        Self {
+            width,
+            height: rows.len(),
+            cells: rows.into_iter().flatten().collect(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<TileId> {
+        self.cells[self.offset(x, y)]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, tile: Option<TileId>) {
+        let offset = self.offset(x, y);
+        self.cells[offset] = tile;
+    }
+
+    /// Exchange the tiles of two cells
+    pub fn swap(&mut self, a: GridPosition, b: GridPosition) {
+        let (a, b) = (self.offset(a.x, a.y), self.offset(b.x, b.y));
+        self.cells.swap(a, b);
+    }
+
+    /// Each row of cells, top to bottom
+    pub fn rows(&self) -> impl Iterator<Item = &[Option<TileId>]> {
+        self.cells.chunks(self.width.max(1))
+    }
+
+    /// Every filled cell with its tile, in row order
+    pub fn placed(&self) -> impl Iterator<Item = (GridPosition, TileId)> + '_ {
+        self.cells.iter().enumerate().filter_map(|(offset, tile)| {
+            tile.map(|tile| {
+                let position = GridPosition::new(offset % self.width, offset / self.width);
+                (position, tile)
+            })
+        })
+    }
+
+    fn offset(&self, x: usize, y: usize) -> usize {
+        assert!(
+            x < self.width && y < self.height,
+            "cell ({x}, {y}) is outside the {}x{} grid",
+            self.width,
+            self.height
+        );
+        y * self.width + x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tile_id_round_trips_and_stays_compact() {
+        assert_eq!(TileId::new(0).index(), 0);
+        assert_eq!(TileId::new(41).index(), 41);
+        assert_eq!(std::mem::size_of::<Option<TileId>>(), 4);
+    }
+
+    #[test]
+    fn test_grid_cells() {
+        let mut grid = TileGrid::new(3, 2);
+        assert_eq!((grid.width(), grid.height()), (3, 2));
+        assert!(grid.placed().next().is_none());
+
+        grid.set(2, 0, Some(TileId::new(5)));
+        grid.set(0, 1, Some(TileId::new(7)));
+        grid.swap(GridPosition::new(2, 0), GridPosition::new(1, 1));
+        assert_eq!(grid.get(2, 0), None);
+        assert_eq!(grid.get(1, 1), Some(TileId::new(5)));
+
+        let placed: Vec<_> = grid.placed().collect();
+        assert_eq!(
+            placed,
+            [
+                (GridPosition::new(0, 1), TileId::new(7)),
+                (GridPosition::new(1, 1), TileId::new(5)),
+            ]
+        );
+        let rows: Vec<_> = grid.rows().collect();
+        assert_eq!(rows[1], [Some(TileId::new(7)), Some(TileId::new(5)), None]);
+
+        assert_eq!(TileGrid::from_rows(vec![vec![None, None]; 2]).width(), 2);
+        assert!(TileGrid::default().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "outside")]
+    fn test_out_of_range_cells_panic() {
+        TileGrid::new(2, 2).get(2, 0);
+    }
+}
@@ -0,0 +1,111 @@
+use anyhow::{bail, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// Default cost of reusing a material, in squared Lab units
+pub const DEFAULT_USAGE_PENALTY_WEIGHT: f32 = 25.0;
+/// Uses past which the exponential curve stops growing, keeping scores finite
+const MAX_EXPONENT: usize = 100;
+
+/// How the cost of using a material again grows with the uses it already has
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UsageCurve {
+    /// Reuse is free until the hard cap
+    #[default]
+    None,
+    /// Each use costs as much as the one before
+    Linear,
+    /// The cost grows with the square of the uses
+    Quadratic,
+    /// The cost doubles with every use
+    Exponential,
+}
+
+impl FromStr for UsageCurve {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "linear" => Ok(Self::Linear),
+            "quadratic" => Ok(Self::Quadratic),
+            "exponential" => Ok(Self::Exponential),
+            _ => bail!(
+                "Unknown usage penalty {s:?} (expected none, linear, quadratic or exponential)"
+            ),
+        }
+    }
+}
+
+impl fmt::Display for UsageCurve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::None => "none",
+            Self::Linear => "linear",
+            Self::Quadratic => "quadratic",
+            Self::Exponential => "exponential",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Soft usage cost added to a candidate's score, so heavily used materials give way to
+/// slightly worse matches instead of being cut off at the cap
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UsagePenalty {
+    pub curve: UsageCurve,
+    /// Cost of the first reuse, in squared Lab units
+    pub weight: f32,
+}
+
+impl Default for UsagePenalty {
+    fn default() -> Self {
+        Self {
+            curve: UsageCurve::None,
+            weight: DEFAULT_USAGE_PENALTY_WEIGHT,
+        }
+    }
+}
+
+impl UsagePenalty {
+    /// Cost of placing a material that has already been used `uses` times; the first use
+    /// is always free
+    pub fn cost(&self, uses: usize) -> f32 {
+        let growth = match self.curve {
+            UsageCurve::None => return 0.0,
+            UsageCurve::Linear => uses as f32,
+            UsageCurve::Quadratic => (uses as f32).powi(2),
+            UsageCurve::Exponential => 2f32.powi(uses.min(MAX_EXPONENT) as i32) - 1.0,
+        };
+        self.weight.max(0.0) * growth
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn penalty(curve: UsageCurve) -> UsagePenalty {
+        UsagePenalty { curve, weight: 2.0 }
+    }
+
+    #[test]
+    fn test_curves() {
+        let costs = |curve| -> Vec<f32> { (0..4).map(|n| penalty(curve).cost(n)).collect() };
+        assert_eq!(costs(UsageCurve::None), [0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(costs(UsageCurve::Linear), [0.0, 2.0, 4.0, 6.0]);
+        assert_eq!(costs(UsageCurve::Quadratic), [0.0, 2.0, 8.0, 18.0]);
+        assert_eq!(costs(UsageCurve::Exponential), [0.0, 2.0, 6.0, 14.0]);
+        assert!(penalty(UsageCurve::Exponential).cost(10_000).is_finite());
+    }
+
+    #[test]
+    fn test_parse_curve() {
+        assert_eq!(
+            "Quadratic".parse::<UsageCurve>().unwrap(),
+            UsageCurve::Quadratic
+        );
+        assert_eq!(UsageCurve::Exponential.to_string(), "exponential");
+        assert!("cubic".parse::<UsageCurve>().is_err());
+    }
+}
@@ -0,0 +1,212 @@
+use anyhow::{bail, Result};
+use image::DynamicImage;
+use std::fmt;
+use std::str::FromStr;
+
+/// Orientation in which a material is shown
+///
+/// Rotations are clockwise, matching `image::imageops`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TileTransform {
+    #[default]
+    Identity,
+    FlipHorizontal,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl TileTransform {
+    pub fn apply(&self, img: &DynamicImage) -> DynamicImage {
+        match self {
+            Self::Identity => img.clone(),
+            Self::FlipHorizontal => img.fliph(),
+            Self::Rotate90 => img.rotate90(),
+            Self::Rotate180 => img.rotate180(),
+            Self::Rotate270 => img.rotate270(),
+        }
+    }
+
+    /// Whether the transform swaps width and height
+    pub fn swaps_axes(&self) -> bool {
+        matches!(self, Self::Rotate90 | Self::Rotate270)
+    }
+
+    /// Dimensions of a `width` x `height` image after the transform
+    pub fn dimensions(&self, width: u32, height: u32) -> (u32, u32) {
+        if self.swaps_axes() {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// Where a normalized point (such as a focus point) ends up after the transform
+    pub fn map_point(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        match self {
+            Self::Identity => (x, y),
+            Self::FlipHorizontal => (1.0 - x, y),
+            Self::Rotate90 => (1.0 - y, x),
+            Self::Rotate180 => (1.0 - x, 1.0 - y),
+            Self::Rotate270 => (y, 1.0 - x),
+        }
+    }
+}
+
+impl fmt::Display for TileTransform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Identity => "identity",
+            Self::FlipHorizontal => "flip",
+            Self::Rotate90 => "rotate90",
+            Self::Rotate180 => "rotate180",
+            Self::Rotate270 => "rotate270",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Which extra orientations each material contributes as match candidates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VariantSet {
+    #[default]
+    None,
+    /// Mirror image
+    Flip,
+    /// 90°, 180° and 270° rotations
+    Rotate,
+    /// Mirror image and rotations
+    All,
+}
+
+impl VariantSet {
+    /// Transforms to add besides the original orientation
+    ///
+    /// Quarter turns swap the aspect ratio, so they are only included when
+    /// `allow_quarter_turns` says the rotated material still fits a cell.
+    pub fn transforms(&self, allow_quarter_turns: bool) -> Vec<TileTransform> {
+        let flip = matches!(self, Self::Flip | Self::All);
+        let rotate = matches!(self, Self::Rotate | Self::All);
+
+        let mut transforms = Vec::new();
+        if flip {
+            transforms.push(TileTransform::FlipHorizontal);
+        }
+        if rotate {
+            if allow_quarter_turns {
+                transforms.push(TileTransform::Rotate90);
+            }
+            transforms.push(TileTransform::Rotate180);
+            if allow_quarter_turns {
+                transforms.push(TileTransform::Rotate270);
+            }
+        }
+        transforms
+    }
+}
+
+impl FromStr for VariantSet {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "flip" => Ok(Self::Flip),
+            "rotate" => Ok(Self::Rotate),
+            "all" => Ok(Self::All),
+            _ => bail!("Unknown tile variants {s:?} (expected none, flip, rotate or all)"),
+        }
+    }
+}
+
+impl fmt::Display for VariantSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::None => "none",
+            Self::Flip => "flip",
+            Self::Rotate => "rotate",
+            Self::All => "all",
+        };
+        f.write_str(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, ImageBuffer, Rgb, RgbImage};
+
+    /// 3x2 image with a single white pixel at (x, y)
+    fn marker_image(x: u32, y: u32) -> DynamicImage {
+        let mut img = RgbImage::new(3, 2);
+        img.put_pixel(x, y, Rgb([255, 255, 255]));
+        DynamicImage::ImageRgb8(img)
+    }
+
+    fn marker_position(img: &DynamicImage) -> (u32, u32) {
+        let rgb = img.to_rgb8();
+        let (x, y, _) = rgb
+            .enumerate_pixels()
+            .find(|(_, _, p)| p[0] == 255)
+            .unwrap();
+        (x, y)
+    }
+
+    #[test]
+    fn test_variant_set_transforms() {
+        assert!(VariantSet::None.transforms(true).is_empty());
+        assert_eq!(
+            VariantSet::Flip.transforms(true),
+            vec![TileTransform::FlipHorizontal]
+        );
+        assert_eq!(VariantSet::All.transforms(true).len(), 4);
+        // Without quarter turns only the half turn is left
+        assert_eq!(
+            VariantSet::Rotate.transforms(false),
+            vec![TileTransform::Rotate180]
+        );
+        assert_eq!("all".parse::<VariantSet>().unwrap(), VariantSet::All);
+        assert!("mirror".parse::<VariantSet>().is_err());
+    }
+
+    #[test]
+    fn test_dimensions_and_apply_agree() {
+        let img = DynamicImage::ImageRgb8(ImageBuffer::new(3, 2));
+        for transform in [
+            TileTransform::Identity,
+            TileTransform::FlipHorizontal,
+            TileTransform::Rotate90,
+            TileTransform::Rotate180,
+            TileTransform::Rotate270,
+        ] {
+            assert_eq!(
+                transform.apply(&img).dimensions(),
+                transform.dimensions(3, 2),
+                "{transform}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_map_point_follows_pixels() {
+        // Pixel centers of a 3x2 image in normalized coordinates
+        let center = |(x, y): (u32, u32), (w, h): (u32, u32)| {
+            ((x as f32 + 0.5) / w as f32, (y as f32 + 0.5) / h as f32)
+        };
+        let img = marker_image(0, 0);
+        for transform in [
+            TileTransform::FlipHorizontal,
+            TileTransform::Rotate90,
+            TileTransform::Rotate180,
+            TileTransform::Rotate270,
+        ] {
+            let transformed = transform.apply(&img);
+            let expected = center(marker_position(&transformed), transformed.dimensions());
+            let mapped = transform.map_point(center((0, 0), (3, 2)));
+            assert!(
+                (mapped.0 - expected.0).abs() < 1e-4 && (mapped.1 - expected.1).abs() < 1e-4,
+                "{transform}: {mapped:?} != {expected:?}"
+            );
+        }
+    }
+}